@@ -0,0 +1,142 @@
+// ~/.nyaa.toml, read as flat `key = value` lines. [section] headers prefix
+// the keys under them, so `[colors]` then `header = "red"` is colors.header.
+// keys may be quoted, like "Software/Games" = "...". that's all the toml this
+// needs, anything else is skipped
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Self {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+        for line in text.lines() {
+            let line = strip_comment(line).trim();
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            if key.is_empty() {
+                continue;
+            }
+            let key = unquote(key);
+            let key = if section.is_empty() {
+                key
+            } else {
+                format!("{}.{}", section, key)
+            };
+            values.insert(key, unquote(value));
+        }
+        Self { values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    // every key = value under [name], in key order
+    pub fn section(&self, name: &str) -> Vec<(&str, &str)> {
+        let prefix = format!("{}.", name);
+        let mut entries: Vec<_> = self
+            .values
+            .iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(&prefix)?, value.as_str())))
+            .collect();
+        entries.sort();
+        entries
+    }
+}
+
+// drop a # comment, unless the # is inside a quoted string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            _ if escaped => escaped = false,
+            ('\\', Some('"')) => escaped = true,
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// "basic" strings take \" and \\ escapes, 'literal' ones are taken as they are
+fn unquote(value: &str) -> String {
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return inner.replace("\\\"", "\"").replace("\\\\", "\\");
+    }
+    match value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        Some(inner) => inner.to_string(),
+        None => value.to_string(),
+    }
+}
+
+pub fn path() -> Option<std::path::PathBuf> {
+    dirs::home_dir().map(|home| home.join(".nyaa.toml"))
+}
+
+// an empty config when there's no file or it can't be read
+pub fn load() -> Config {
+    path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|text| Config::parse(&text))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys_sections_and_comments() {
+        let config = Config::parse(
+            r##"
+            # where the api lives
+            api_url = "http://localhost:8080" # self-hosted
+            refresh = 30
+            quote = "a \"#1\" pick"
+
+            [colors]
+            header = 'red'
+            not a key
+            "##,
+        );
+        assert_eq!(config.get("api_url"), Some("http://localhost:8080"));
+        assert_eq!(config.get("refresh"), Some("30"));
+        assert_eq!(config.get("quote"), Some("a \"#1\" pick"));
+        assert_eq!(config.get("colors.header"), Some("red"));
+        assert_eq!(config.get("header"), None);
+        assert_eq!(Config::parse("").get("api_url"), None);
+        assert!(config.section("keys").is_empty());
+    }
+
+    #[test]
+    fn sections_list_their_keys() {
+        let config = Config::parse(
+            r#"
+            top = 1
+            [category_commands]
+            "Software/Games" = "qbt {magnet}"
+            Anime = "aria2c {magnet}"
+            "#,
+        );
+        assert_eq!(
+            config.section("category_commands"),
+            [
+                ("Anime", "aria2c {magnet}"),
+                ("Software/Games", "qbt {magnet}")
+            ]
+        );
+    }
+}
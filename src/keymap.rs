@@ -0,0 +1,156 @@
+// remappable keys for the table, set under [keys] in ~/.nyaa.toml like
+// `next_page = "N"`. a remapped key is turned back into the key its action
+// has by default before dispatch, so the handlers only know the defaults.
+// counts, arrows, enter and the keys inside popups can't be remapped
+use crate::config::Config;
+use crossterm::event::KeyCode;
+use std::collections::HashMap;
+
+// every action that can be moved, and the key it's on by default
+const ACTIONS: [(&str, char); 52] = [
+    ("quit", 'q'),
+    ("down", 'j'),
+    ("up", 'k'),
+    ("first", 'g'),
+    ("last", 'G'),
+    ("next_page", 'n'),
+    ("prev_page", 'p'),
+    ("go_to_page", ':'),
+    ("search", '/'),
+    ("clear_search", 'b'),
+    ("reload", 'R'),
+    ("help", 'h'),
+    ("details", 'd'),
+    ("full_name", 'E'),
+    ("open", 'o'),
+    ("magnet", 'm'),
+    ("torrent", 't'),
+    ("copy_magnet", 'y'),
+    ("copy_id", 'I'),
+    ("copy_name", 'N'),
+    ("download_and_open", 'a'),
+    ("download", 'D'),
+    ("trackers", 'T'),
+    ("api_url", 'u'),
+    ("save_search", 'w'),
+    ("saved_searches", 'W'),
+    ("history", 'H'),
+    ("categories", 'c'),
+    ("category_tree", 'C'),
+    ("filter", 'x'),
+    ("log", 'L'),
+    ("review_marked", 'v'),
+    ("sort_column", ','),
+    ("reverse", 'r'),
+    ("cycle_sort", 'S'),
+    ("trend_column", '~'),
+    ("hash_column", '#'),
+    ("category_column", '%'),
+    ("relative_dates", '@'),
+    ("scope", 'z'),
+    ("min_seeders", 'M'),
+    ("fuzzy_filter", '&'),
+    ("first_unviewed", 'U'),
+    ("next_unviewed", ']'),
+    ("viewed", 's'),
+    ("session_marks", 'e'),
+    ("mark", ' '),
+    ("bookmark", 'f'),
+    ("bookmarks", 'F'),
+    ("aria2", 'A'),
+    ("copy_json", 'J'),
+    ("export", 'X'),
+];
+
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    // the key pressed to the default key of its action
+    keys: HashMap<char, char>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+impl KeyMap {
+    pub fn from_config(config: &Config) -> Self {
+        let mut keys = HashMap::new();
+        let mut moved = vec![];
+        for (action, default) in ACTIONS {
+            match config.get(&format!("keys.{}", action)).and_then(parse_key) {
+                Some(key) => moved.push((key, default)),
+                None => {
+                    keys.insert(default, default);
+                }
+            }
+        }
+        // a key set in the config wins over an action that's there by default
+        keys.extend(moved);
+        Self { keys }
+    }
+
+    // the key the handlers know this one as. a default key whose action
+    // moved elsewhere does nothing, unless another action moved onto it
+    pub fn resolve(&self, code: KeyCode) -> KeyCode {
+        let c = match code {
+            KeyCode::Char(c) => c,
+            code => return code,
+        };
+        match self.keys.get(&c) {
+            Some(default) => KeyCode::Char(*default),
+            None if ACTIONS.iter().any(|(_, default)| *default == c) => KeyCode::Null,
+            None => code,
+        }
+    }
+}
+
+// a single character, or "space"
+fn parse_key(value: &str) -> Option<char> {
+    if value.eq_ignore_ascii_case("space") {
+        return Some(' ');
+    }
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_ascii_digit() => Some(c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remapped_keys_resolve_to_the_defaults() {
+        let keymap = KeyMap::default();
+        assert_eq!(keymap.resolve(KeyCode::Char('j')), KeyCode::Char('j'));
+        assert_eq!(keymap.resolve(KeyCode::Char('Z')), KeyCode::Char('Z'));
+        assert_eq!(keymap.resolve(KeyCode::Enter), KeyCode::Enter);
+
+        // colemak-ish: n/e for down/up, with next_page and session_marks moved off them
+        let config = Config::parse(
+            r#"
+            [keys]
+            down = "n"
+            up = "e"
+            next_page = "N"
+            session_marks = "E"
+            mark = "space"
+            help = "12"
+            "#,
+        );
+        let keymap = KeyMap::from_config(&config);
+        assert_eq!(keymap.resolve(KeyCode::Char('n')), KeyCode::Char('j'));
+        assert_eq!(keymap.resolve(KeyCode::Char('e')), KeyCode::Char('k'));
+        // N is copy_name's by default, the config takes it for next_page
+        assert_eq!(keymap.resolve(KeyCode::Char('N')), KeyCode::Char('n'));
+        assert_eq!(keymap.resolve(KeyCode::Char('E')), KeyCode::Char('e'));
+        assert_eq!(keymap.resolve(KeyCode::Char(' ')), KeyCode::Char(' '));
+        // j and k have no action left, and "12" is no key so help stays on h
+        assert_eq!(keymap.resolve(KeyCode::Char('j')), KeyCode::Null);
+        assert_eq!(keymap.resolve(KeyCode::Char('k')), KeyCode::Null);
+        assert_eq!(keymap.resolve(KeyCode::Char('h')), KeyCode::Char('h'));
+    }
+}
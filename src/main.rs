@@ -1,87 +1,869 @@
+mod config;
+mod keymap;
+mod nyaa;
+#[cfg(test)]
+mod testing;
+mod theme;
+
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    cursor::{self, MoveTo},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    style::Print,
+    terminal::{
+        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen, SetTitle,
+    },
 };
+use nyaa::{parse_count, Params, Response, Responses};
 use serde::Deserialize;
 use serde::Serialize;
-use std::fs::File;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io::prelude::*;
+use std::time::{Duration, Instant};
 use std::{error::Error, io};
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
+    widgets::{
+        Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap,
+    },
     Frame, Terminal,
 };
 
-const NYAA_URL: &str = "https://nyaa-api.fly.dev";
+// how long to wait before retrying an empty answer from a cold backend
+const COLD_START_DELAY: Duration = Duration::from_millis(1500);
+
+// how long typing has to pause before a live search goes out
+const LIVE_SEARCH_DELAY: Duration = Duration::from_millis(300);
+
+// NYAA_OPENER, like "firefox --new-tab", or the platform's "open this with
+// whatever handles it" command
+fn opener() -> (String, Vec<String>) {
+    opener_for(std::env::var("NYAA_OPENER").ok().as_deref())
+}
+
+// rundll32 rather than cmd /C start on windows, since cmd would split urls on &
+fn opener_for(setting: Option<&str>) -> (String, Vec<String>) {
+    let mut words = setting
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string);
+    if let Some(program) = words.next() {
+        return (program, words.collect());
+    }
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+        ("open", &[])
+    } else if cfg!(windows) {
+        ("rundll32", &["url.dll,FileProtocolHandler"])
+    } else {
+        ("xdg-open", &[])
+    };
+    (
+        program.to_string(),
+        args.iter().map(|a| a.to_string()).collect(),
+    )
+}
 
-fn open_url(url: &str) {
+fn open_url(url: &str) -> io::Result<()> {
     use std::process::Command;
 
-    Command::new("xdg-open")
-        .arg(url)
-        .output()
-        .expect("failed to execute process");
+    let (program, args) = opener();
+    let output = Command::new(&program).args(args).arg(url).output()?;
+    if !output.status.success() {
+        let message = format!("{} exited with {}", program, output.status);
+        return Err(io::Error::other(message));
+    }
+    Ok(())
 }
 
-#[derive(Clone, Debug)]
-struct Params {
-    page: u16,
-    query: String,
+// wrap s in single quotes so sh passes it through untouched
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// a command template with {magnet}, {torrent} and {url} (the item's page on
+// nyaa.si) filled in, each quoted for sh
+fn fill_command(template: &str, item: &Response) -> String {
+    let url = format!("https://nyaa.si/view/{}", item.id);
+    template
+        .replace("{magnet}", &shell_quote(&item.magnet))
+        .replace("{torrent}", &shell_quote(&item.torrent))
+        .replace("{url}", &shell_quote(&url))
 }
 
-impl Params {
-    pub fn new() -> Self {
-        Self {
-            page: 1,
-            query: "".to_string(),
+// run a shell command without letting its output onto the tui, or in its own
+// tmux window where it can be watched
+fn run_command(command: &str, tmux: bool) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    if tmux {
+        let status = Command::new("tmux")
+            .args(["new-window", "-n", "nyaa", command])
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("tmux exited with {}", status)));
         }
+        return Ok(());
     }
 
-    pub fn next_page_by(&mut self, amount: u16) {
-        let page = self.page;
-        self.page = if page + amount < 1000 {
-            page + amount
-        } else {
-            1000
+    Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+// ~/Downloads, or wherever the platform keeps downloads
+fn default_download_dir() -> std::path::PathBuf {
+    dirs::download_dir()
+        .or_else(|| dirs::home_dir().map(|home| home.join("Downloads")))
+        .unwrap_or_else(|| ".".into())
+}
+
+// a directory from the environment, then ~/.nyaa.toml (where a leading ~/
+// is the home directory)
+fn dir_setting(config: &config::Config, var: &str, key: &str) -> Option<std::path::PathBuf> {
+    if let Some(dir) = std::env::var_os(var).filter(|d| !d.is_empty()) {
+        return Some(dir.into());
+    }
+    match config.get(key).map(str::trim) {
+        Some(dir) if !dir.is_empty() => match (dir.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(dir.into()),
+        },
+        _ => None,
+    }
+}
+
+// where .torrent files get saved: NYAA_DOWNLOAD_DIR, then download_dir, then the default
+fn download_dir(config: &config::Config) -> std::path::PathBuf {
+    dir_setting(config, "NYAA_DOWNLOAD_DIR", "download_dir").unwrap_or_else(default_download_dir)
+}
+
+// write items as json to name in dir, or to a timestamped file when name is
+// empty. a .json is added if the name doesn't end in one
+fn export_items(
+    items: &[Response],
+    dir: &std::path::Path,
+    name: &str,
+    now: u64,
+) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    let name = match name.trim() {
+        "" => format!("nyaa-{}.json", now),
+        name if name.ends_with(".json") => sanitize_filename(name),
+        name => format!("{}.json", sanitize_filename(name)),
+    };
+    let json = serde_json::to_string_pretty(items)?;
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(name);
+    std::fs::write(&path, json)?;
+    Ok(path)
+}
+
+// torrent names are free text, so keep them from escaping the directory
+fn sanitize_filename(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        "torrent".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+// an aria2 input file for the items, each magnet followed by a dir= under
+// base named after its category, if there's a base. without one aria2 saves
+// where it's run. there's no out=, aria2 ignores it for torrents
+fn aria2_input(items: &[Response], base: Option<&std::path::Path>) -> String {
+    let mut input = String::new();
+    for item in items.iter().filter(|i| !i.magnet.is_empty()) {
+        input.push_str(&item.magnet);
+        input.push('\n');
+        let category: Vec<&str> = [item.category.as_str(), item.sub_category.as_str()]
+            .into_iter()
+            .filter(|c| !c.is_empty())
+            .collect();
+        if let Some(base) = base.filter(|_| !category.is_empty()) {
+            let dir = base.join(sanitize_filename(&category.join(" - ")));
+            input.push_str(&format!("  dir={}\n", dir.display()));
+        }
+    }
+    input
+}
+
+// the magnets of the items that have one, in order
+fn marked_magnets(items: &[Response]) -> Vec<&str> {
+    items
+        .iter()
+        .map(|i| i.magnet.as_str())
+        .filter(|m| !m.is_empty())
+        .collect()
+}
+
+// the items as a json array for scripts, size_bytes is null when unknown
+fn magnets_json(items: &[Response]) -> String {
+    let items: Vec<serde_json::Value> = items
+        .iter()
+        .map(|item| {
+            serde_json::json!({
+                "name": item.name,
+                "magnet": item.magnet,
+                "hash": item.hash,
+                "size_bytes": parse_filesize(&item.filesize),
+            })
+        })
+        .collect();
+    serde_json::Value::from(items).to_string()
+}
+
+// save the item's .torrent into dir, named after the torrent
+async fn download_torrent(
+    client: &nyaa::Client,
+    item: &Response,
+    dir: &std::path::Path,
+) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    let bytes = client.download(&item.torrent).await?;
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.torrent", sanitize_filename(&item.name)));
+    std::fs::write(&path, &bytes)?;
+    Ok(path)
+}
+
+// there's no portable clipboard api, so hand the text to whichever tool exists
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    const CLIPBOARD_COMMANDS: [(&str, &[&str]); 5] = [
+        ("pbcopy", &[]),
+        ("clip", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+    ];
+
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut child) = child {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes())?;
+            }
+            if child.wait()?.success() {
+                return Ok(());
+            }
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no clipboard tool found (pbcopy, clip, wl-copy, xclip or xsel)",
+    ))
+}
+
+// turn a file of nyaa ids, view urls or magnets (one per line) into items,
+// along with how many non-blank lines were none of those
+fn parse_import(text: &str) -> (Responses, usize) {
+    let mut items = vec![];
+    let mut skipped = 0;
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let id = line
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()));
+        if let Some(id) = id {
+            items.push(Response {
+                id: id.to_string(),
+                name: format!("nyaa #{}", id),
+                torrent: format!("https://nyaa.si/download/{}.torrent", id),
+                ..Default::default()
+            });
+            continue;
         }
+        let magnet = match reqwest::Url::parse(line) {
+            Ok(url) if url.scheme() == "magnet" => url,
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
+        let param = |name: &str| {
+            magnet
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        let hash = param("xt")
+            .map(|xt| xt.trim_start_matches("urn:btih:").to_string())
+            .unwrap_or_default();
+        items.push(Response {
+            name: param("dn").unwrap_or_else(|| hash.clone()),
+            hash,
+            magnet: line.to_string(),
+            ..Default::default()
+        });
     }
+    (items, skipped)
+}
 
-    pub fn prev_page_by(&mut self, amount: u16) {
-        let page = self.page;
-        self.page = if page <= amount { 0 } else { page - amount }
+// the tr= params of a magnet link, decoded
+fn magnet_trackers(magnet: &str) -> Vec<String> {
+    match reqwest::Url::parse(magnet) {
+        Ok(url) => url
+            .query_pairs()
+            .filter(|(key, _)| key == "tr")
+            .map(|(_, value)| value.into_owned())
+            .collect(),
+        Err(_) => vec![],
     }
+}
+
+// nyaa's category tree, as (id, name) with each main category's subs
+struct Category {
+    id: &'static str,
+    name: &'static str,
+    subs: &'static [(&'static str, &'static str)],
+}
+
+const CATEGORIES: [Category; 6] = [
+    Category {
+        id: "1",
+        name: "Anime",
+        subs: &[
+            ("1", "Anime Music Video"),
+            ("2", "English-translated"),
+            ("3", "Non-English-translated"),
+            ("4", "Raw"),
+        ],
+    },
+    Category {
+        id: "2",
+        name: "Audio",
+        subs: &[("1", "Lossless"), ("2", "Lossy")],
+    },
+    Category {
+        id: "3",
+        name: "Literature",
+        subs: &[
+            ("1", "English-translated"),
+            ("2", "Non-English-translated"),
+            ("3", "Raw"),
+        ],
+    },
+    Category {
+        id: "4",
+        name: "Live Action",
+        subs: &[
+            ("1", "English-translated"),
+            ("2", "Idol/Promotional Video"),
+            ("3", "Non-English-translated"),
+            ("4", "Raw"),
+        ],
+    },
+    Category {
+        id: "5",
+        name: "Pictures",
+        subs: &[("1", "Graphics"), ("2", "Photos")],
+    },
+    Category {
+        id: "6",
+        name: "Software",
+        subs: &[("1", "Applications"), ("2", "Games")],
+    },
+];
+
+// "Anime / Raw" for a category and sub category id, None for all categories
+fn category_label(category: Option<&str>, sub_category: Option<&str>) -> Option<String> {
+    let main = CATEGORIES.iter().find(|c| Some(c.id) == category)?;
+    let sub = main.subs.iter().find(|(id, _)| Some(*id) == sub_category);
+    Some(match sub {
+        Some((_, name)) => format!("{} / {}", main.name, name),
+        None => main.name.to_string(),
+    })
+}
 
-    pub fn set_query<S: Into<String> + std::fmt::Display>(&mut self, query: S) {
-        self.query = query.to_string();
+// every category and sub category as one flat list for c, no filter first
+fn category_choices() -> Vec<(Option<&'static str>, Option<&'static str>)> {
+    let mut choices = vec![(None, None)];
+    for category in &CATEGORIES {
+        choices.push((Some(category.id), None));
+        for (sub, _) in category.subs {
+            choices.push((Some(category.id), Some(*sub)));
+        }
     }
+    choices
 }
 
-#[derive(Clone)]
 struct App {
     state: TableState,
+    // what's on screen, page_items after scope, filters and sorting
     items: Responses,
+    // the page as it was fetched
+    page_items: Responses,
+    // hide rows with fewer seeders than this
+    min_seeders: Option<u32>,
     current: Option<usize>,
+    // everything up to this id counts as viewed, from before marks were per item
     last_id: u64,
+    // items marked viewed (true) or not (false) with s, which wins over last_id
+    viewed: BTreeMap<u64, bool>,
+    // talks to the nyaa-api instance, and fetches .torrent files. there's one
+    // for the session so paging reuses its connections instead of paying for a
+    // new tls handshake each time, a few ms of cpu plus a round trip or two
+    client: nyaa::Client,
+    // where a and D save .torrent files and A writes its aria2 input
+    download_dir: std::path::PathBuf,
+    // NYAA_DOWNLOAD_DIR or download_dir chose it, so aria2 is told to use it too
+    download_dir_set: bool,
+    // ~/.nyaa as last written, which doesn't see session-only marks
+    saved: State,
+    // retry an empty result once, on the session's first fetch only, in case
+    // the backend was still cold-starting
+    retry_empty: bool,
+    // the first fetch has been made, whatever it got
+    fetched: bool,
+    // the backend has answered with something
+    warm: bool,
+    sort: Option<(SortKey, bool)>,
+    // NYAA_DEFAULT_SORT or default_sort, used for every fetch until a sort is picked
+    default_sort: Option<(SortKey, bool)>,
+    // plain ascii markers for terminals without emoji
+    ascii: bool,
+    searches: Vec<SavedSearch>,
+    extra_columns: Vec<ColumnKind>,
+    trend: HashMap<String, Vec<u64>>,
+    // refetch the page this often, if set
+    refresh: Option<Duration>,
+    // NYAA_MAGNET_COMMAND or magnet_command in ~/.nyaa.toml, run for m with
+    // the placeholders of fill_command filled in
+    magnet_command: Option<String>,
+    // NYAA_TORRENT_COMMAND or torrent_command, run for t the same way
+    torrent_command: Option<String>,
+    // NYAA_CATEGORY_COMMANDS or [category_commands], (category or
+    // category/sub category, command) to use for m instead of magnet_command
+    category_commands: Vec<(String, String)>,
+    tmux: bool,
+    // ask before s flips more rows than this
+    confirm_mark: usize,
+    // items marked with space, kept whole so they survive paging
+    marked: Responses,
+    // items starred with f, kept whole in ~/.nyaa_bookmarks.json
+    bookmarks: Responses,
+    // F swaps the page for the bookmarks
+    show_bookmarks: bool,
+    // the shortest time between redraws while events are queued
+    frame_interval: Duration,
+    // external programs launched within this long of the last one are skipped
+    launch_cooldown: Duration,
+    launched: Option<Instant>,
+    // whether sorting and marking see this page or every page fetched so far
+    scope: Scope,
+    // the pages fetched for pages_search, kept for Scope::All
+    pages: BTreeMap<u16, Responses>,
+    pages_search: Option<(String, Option<String>, Option<String>, u8)>,
+    // what happened this session, oldest first, as (unix time, entry)
+    log: VecDeque<(u64, String)>,
+    // false once a write to the home directory failed, state is memory only after that
+    saving: bool,
+    // NYAA_EXACT_SIZES=1 shows the byte count in the detail view
+    exact_sizes: bool,
+    // s only marks for this session, ~/.nyaa is left alone
+    ephemeral: bool,
+    // draw a scrollbar over the table's right border
+    scrollbar: bool,
+    // seeder counts under the first are red, from the second on green
+    seeder_colors: (u64, u64),
+    // show dates as "3d ago" rather than as the api sent them
+    relative_dates: bool,
+    // [keys] from ~/.nyaa.toml
+    keymap: keymap::KeyMap,
+    // [colors] in ~/.nyaa.toml
+    theme: theme::Theme,
+    // where the last frame drew the table's rows and which items it showed,
+    // for finding the item under a click
+    table_rows: Rect,
+    table_window: (usize, usize),
+    // the last left click and the item it hit, for spotting double clicks
+    last_click: Option<(Instant, usize)>,
+    // search as the query is typed rather than on enter
+    live_search: bool,
+    // the search and page up before / in live mode, put back by esc
+    before_search: Option<(Params, Responses)>,
+    // the next page, fetched in the background once the selection nears the
+    // end of this one, and when. only used if the search is still the same
+    // by then and it isn't older than PREFETCH_MAX_AGE
+    prefetch: Option<(Params, Instant, PageTask)>,
+    // q asks first instead of quitting straight away
+    confirm_quit: bool,
+    // NYAA_EXPORT_DIR or export_dir, where X writes, the download dir if unset
+    export_dir: Option<std::path::PathBuf>,
+    // when the cached page on screen was fetched, while the api is unreachable
+    offline: Option<u64>,
+    // typed after &, names have to contain its letters in order
+    fuzzy: String,
 }
 
-fn get_last_id() -> std::io::Result<u64> {
-    let home_dir = dirs::home_dir();
-    if let Some(home) = home_dir {
-        if let Ok(id) = std::fs::read_to_string(&format!("{}/.nyaa", home.display())) {
-            let id = id.trim();
-            let id = id.parse::<u64>().unwrap_or(0);
-            Ok(id)
-        } else {
-            Ok(0)
-        }
+// how close to the end of the page the selection gets before the next one is fetched
+const PREFETCH_MARGIN: usize = 5;
+
+// a page being fetched in the background
+type PageTask = tokio::task::JoinHandle<Result<Responses, nyaa::ApiError>>;
+
+// a prefetched page older than this is fetched again instead
+const PREFETCH_MAX_AGE: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scope {
+    Page,
+    All,
+}
+
+// NYAA_CATEGORY_COMMANDS, then the [category_commands] table in ~/.nyaa.toml,
+// where each key is a category or category/sub category
+fn category_commands(config: &config::Config) -> Vec<(String, String)> {
+    match std::env::var("NYAA_CATEGORY_COMMANDS") {
+        Ok(value) => parse_category_commands(&value),
+        Err(_) => config
+            .section("category_commands")
+            .into_iter()
+            .filter_map(|(category, command)| category_command(category, command))
+            .collect(),
+    }
+}
+
+// "Anime=aria2c {magnet}; Software/Games=qbittorrent {magnet}" as pairs,
+// skipping entries without a category or a command
+fn parse_category_commands(value: &str) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|entry| {
+            let (category, command) = entry.split_once('=')?;
+            category_command(category, command)
+        })
+        .collect()
+}
+
+fn category_command(category: &str, command: &str) -> Option<(String, String)> {
+    let category = category
+        .split('/')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("/");
+    let command = command.trim();
+    if category.is_empty() || command.is_empty() {
+        return None;
+    }
+    Some((category, command.to_string()))
+}
+
+// NYAA_LAUNCH_COOLDOWN_MS, then launch_cooldown_ms in ~/.nyaa.toml, 300 by default
+fn launch_cooldown(config: &config::Config) -> Duration {
+    let millis = parsed_setting(config, "NYAA_LAUNCH_COOLDOWN_MS", "launch_cooldown_ms");
+    Duration::from_millis(millis.unwrap_or(300))
+}
+
+fn api_url(config: &config::Config) -> String {
+    api_url_for(std::env::var("NYAA_API_URL").ok().as_deref(), config)
+}
+
+// NYAA_API_URL, then api_url in ~/.nyaa.toml, then the public instance
+fn api_url_for(env: Option<&str>, config: &config::Config) -> String {
+    let set = |url: &str| Some(url.trim().to_string()).filter(|url| !url.is_empty());
+    env.and_then(set)
+        .or_else(|| config.get("api_url").and_then(set))
+        .unwrap_or_else(|| nyaa::NYAA_URL.to_string())
+}
+
+// a command template from the environment, then ~/.nyaa.toml
+fn command_setting(config: &config::Config, var: &str, key: &str) -> Option<String> {
+    let set = |command: &str| Some(command.trim().to_string()).filter(|c| !c.is_empty());
+    match std::env::var(var) {
+        Ok(command) => set(&command),
+        Err(_) => config.get(key).and_then(set),
+    }
+}
+
+// a number from the environment, then ~/.nyaa.toml, skipping either when it
+// doesn't parse
+fn parsed_setting<T: std::str::FromStr>(
+    config: &config::Config,
+    var: &str,
+    key: &str,
+) -> Option<T> {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .or_else(|| config.get(key).and_then(|v| v.trim().parse().ok()))
+}
+
+// NYAA_RETRIES, then retries in ~/.nyaa.toml, then 2
+fn retries(config: &config::Config) -> u32 {
+    parsed_setting(config, "NYAA_RETRIES", "retries").unwrap_or(nyaa::DEFAULT_RETRIES)
+}
+
+// NYAA_RATE_LIMIT=<requests per second>, then rate_limit in ~/.nyaa.toml,
+// 2 by default and 0 for none
+fn rate_limit(config: &config::Config) -> f64 {
+    parsed_setting::<f64>(config, "NYAA_RATE_LIMIT", "rate_limit")
+        .filter(|r| r.is_finite())
+        .unwrap_or(nyaa::DEFAULT_RATE_LIMIT)
+}
+
+// NYAA_TIMEOUT=<seconds>, then timeout in ~/.nyaa.toml, then 10
+fn timeout(config: &config::Config) -> Duration {
+    parsed_setting::<f64>(config, "NYAA_TIMEOUT", "timeout")
+        .filter(|secs| secs.is_finite() && *secs > 0.0)
+        .map_or(nyaa::DEFAULT_TIMEOUT, Duration::from_secs_f64)
+}
+
+// NYAA_DEFAULT_SORT=<key>[:asc|:desc], then default_sort in ~/.nyaa.toml,
+// sorts every page as it arrives
+fn default_sort(config: &config::Config) -> Option<(SortKey, bool)> {
+    std::env::var("NYAA_DEFAULT_SORT")
+        .ok()
+        .and_then(|v| parse_sort_setting(&v))
+        .or_else(|| config.get("default_sort").and_then(parse_sort_setting))
+}
+
+fn parse_sort_setting(value: &str) -> Option<(SortKey, bool)> {
+    let (name, direction) = match value.trim().split_once(':') {
+        Some((name, direction)) => (name, Some(direction)),
+        None => (value.trim(), None),
+    };
+    let key = SortKey::from_name(&name.to_lowercase())?;
+    let ascending = match direction {
+        None => key.default_ascending(),
+        Some("asc") => true,
+        Some("desc") => false,
+        Some(_) => return None,
+    };
+    Some((key, ascending))
+}
+
+// NYAA_MAX_FPS, then max_fps in ~/.nyaa.toml, caps redraws during bursts of
+// input, 30 by default, 0 for no cap
+fn frame_interval(config: &config::Config) -> Duration {
+    let fps: u32 = parsed_setting(config, "NYAA_MAX_FPS", "max_fps").unwrap_or(30);
+    if fps == 0 {
+        Duration::ZERO
+    } else {
+        Duration::from_secs(1) / fps
+    }
+}
+
+// "<red below>,<green from>" as a pair, like "5,50"
+fn parse_seeder_colors(value: &str) -> Option<(u64, u64)> {
+    let (dead, healthy) = value.split_once(',')?;
+    let dead = dead.trim().parse().ok()?;
+    let healthy = healthy.trim().parse().ok()?;
+    Some((dead, healthy)).filter(|_| dead <= healthy)
+}
+
+// NYAA_SEEDER_COLORS, then seeder_colors in ~/.nyaa.toml, then red for
+// none and green from 10
+fn seeder_colors(config: &config::Config) -> (u64, u64) {
+    std::env::var("NYAA_SEEDER_COLORS")
+        .ok()
+        .and_then(|v| parse_seeder_colors(&v))
+        .or_else(|| config.get("seeder_colors").and_then(parse_seeder_colors))
+        .unwrap_or((1, 10))
+}
+
+// red for a dead swarm, green for a healthy one, yellow in between
+fn seeder_color(seeders: &str, (dead, healthy): (u64, u64)) -> Option<Color> {
+    let seeders = parse_count(seeders)?;
+    Some(if seeders < dead {
+        Color::Red
+    } else if seeders >= healthy {
+        Color::Green
+    } else {
+        Color::Yellow
+    })
+}
+
+// NYAA_CONFIRM_MARK=<rows>, then confirm_mark in ~/.nyaa.toml, sets when s
+// asks first, 10 by default
+fn confirm_mark_threshold(config: &config::Config) -> usize {
+    parsed_setting(config, "NYAA_CONFIRM_MARK", "confirm_mark").unwrap_or(10)
+}
+
+// NYAA_REFRESH=<seconds> refetches the current page on a timer
+fn refresh_interval() -> Option<Duration> {
+    let seconds = std::env::var("NYAA_REFRESH")
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    if seconds == 0 {
+        None
     } else {
-        Ok(0)
+        Some(Duration::from_secs(seconds))
+    }
+}
+
+// NYAA_SCROLLBAR=0, or scrollbar = false in ~/.nyaa.toml, turns off the
+// scrollbar on long lists
+fn scrollbar_enabled(config: &config::Config) -> bool {
+    let off = |v: &str| matches!(v.trim(), "0" | "false" | "no" | "off");
+    match std::env::var("NYAA_SCROLLBAR") {
+        Ok(v) => !off(&v),
+        Err(_) => !config.get("scrollbar").is_some_and(off),
+    }
+}
+
+// NYAA_EXACT_SIZES=1 adds the exact byte count to sizes in the detail view
+fn exact_sizes_enabled() -> bool {
+    match std::env::var("NYAA_EXACT_SIZES") {
+        Ok(v) => matches!(v.trim(), "1" | "true" | "yes" | "on"),
+        Err(_) => false,
+    }
+}
+
+// NYAA_LIVE_SEARCH=1 searches while typing, once the keys pause for a moment
+fn live_search_enabled() -> bool {
+    match std::env::var("NYAA_LIVE_SEARCH") {
+        Ok(v) => matches!(v.trim(), "1" | "true" | "yes" | "on"),
+        Err(_) => false,
+    }
+}
+
+// NYAA_COMPRESSION=0, or compression = false in ~/.nyaa.toml, stops asking
+// the api for gzip or deflate answers
+fn compression_enabled(config: &config::Config) -> bool {
+    let off = |v: &str| matches!(v.trim(), "0" | "false" | "no" | "off");
+    match std::env::var("NYAA_COMPRESSION") {
+        Ok(v) => !off(&v),
+        Err(_) => !config.get("compression").is_some_and(off),
+    }
+}
+
+// NYAA_CONFIRM_QUIT=1, or confirm_quit = true in ~/.nyaa.toml, asks before q quits
+fn confirm_quit_enabled(config: &config::Config) -> bool {
+    let on = |v: &str| matches!(v.trim(), "1" | "true" | "yes" | "on");
+    match std::env::var("NYAA_CONFIRM_QUIT") {
+        Ok(v) => on(&v),
+        Err(_) => config.get("confirm_quit").is_some_and(on),
+    }
+}
+
+// NYAA_ASCII=1 swaps the emoji and dots for plain ascii
+fn ascii_enabled() -> bool {
+    match std::env::var("NYAA_ASCII") {
+        Ok(v) => matches!(v.trim(), "1" | "true" | "yes" | "on"),
+        Err(_) => false,
+    }
+}
+
+// NYAA_EMOJI_WIDTH=1|2 skips probing for how wide the terminal draws emoji
+fn emoji_width_override() -> Option<u16> {
+    std::env::var("NYAA_EMOJI_WIDTH").ok()?.trim().parse().ok()
+}
+
+// some terminals draw ✅ one cell wide while tui lays it out as two, which
+// shifts every column after it. print one and see where the cursor lands
+fn probe_emoji_width<W: Write>(out: &mut W) -> Option<u16> {
+    execute!(out, MoveTo(0, 0), Print("✅")).ok()?;
+    let width = cursor::position().ok().map(|(column, _)| column);
+    execute!(out, Clear(ClearType::All), MoveTo(0, 0)).ok()?;
+    width
+}
+
+// NYAA_RETRY_EMPTY=0 turns off the cold-start retry
+fn retry_empty_enabled() -> bool {
+    match std::env::var("NYAA_RETRY_EMPTY") {
+        Ok(v) => !matches!(v.trim(), "0" | "false" | "no" | "off"),
+        Err(_) => true,
+    }
+}
+
+// NYAA_TMUX=1 runs commands in a new tmux window when we're inside tmux
+fn tmux_enabled() -> bool {
+    let wanted = match std::env::var("NYAA_TMUX") {
+        Ok(v) => matches!(v.trim(), "1" | "true" | "yes" | "on"),
+        Err(_) => false,
+    };
+    wanted && std::env::var_os("TMUX").is_some()
+}
+
+// what ~/.nyaa keeps between sessions
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct State {
+    // the old single viewed mark, now only read
+    #[serde(default)]
+    last_id: u64,
+    #[serde(default)]
+    viewed: BTreeMap<u64, bool>,
+    #[serde(default)]
+    last_query: String,
+    // searches typed with /, newest first
+    #[serde(default)]
+    history: Vec<String>,
+}
+
+// how many searches the history keeps
+const HISTORY_LEN: usize = 20;
+
+// where the state files go. the tests get a scratch directory of their own
+// so running them never touches a real ~/.nyaa
+#[cfg(not(test))]
+fn home_dir() -> Option<std::path::PathBuf> {
+    dirs::home_dir()
+}
+
+#[cfg(test)]
+fn home_dir() -> Option<std::path::PathBuf> {
+    let dir = std::env::temp_dir().join(format!("nyaa-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+fn state_path() -> Option<std::path::PathBuf> {
+    home_dir().map(|home| home.join(".nyaa"))
+}
+
+// ~/.nyaa used to hold nothing but the last id, which still reads as that
+fn parse_state(text: &str) -> State {
+    let text = text.trim();
+    match text.parse::<u64>() {
+        Ok(last_id) => State {
+            last_id,
+            ..State::default()
+        },
+        Err(_) => serde_json::from_str(text).unwrap_or_default(),
+    }
+}
+
+fn load_state() -> State {
+    state_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|text| parse_state(&text))
+        .unwrap_or_default()
+}
+
+fn save_state(state: &State) -> io::Result<()> {
+    if let Some(path) = state_path() {
+        std::fs::write(path, serde_json::to_string(state)?)?;
     }
+    Ok(())
 }
 
 impl App {
@@ -89,314 +871,4951 @@ impl App {
         App {
             state: TableState::default(),
             items: vec![],
+            page_items: vec![],
+            min_seeders: None,
             current: None,
             last_id: 0,
+            viewed: BTreeMap::new(),
+            client: nyaa::Client::with_base(nyaa::NYAA_URL),
+            download_dir: default_download_dir(),
+            download_dir_set: false,
+            saved: State::default(),
+            retry_empty: true,
+            fetched: false,
+            warm: false,
+            sort: None,
+            default_sort: None,
+            ascii: false,
+            searches: vec![],
+            extra_columns: vec![],
+            trend: HashMap::new(),
+            refresh: None,
+            magnet_command: None,
+            torrent_command: None,
+            category_commands: vec![],
+            tmux: false,
+            confirm_mark: 10,
+            marked: vec![],
+            bookmarks: vec![],
+            show_bookmarks: false,
+            frame_interval: Duration::ZERO,
+            launch_cooldown: Duration::from_millis(300),
+            launched: None,
+            scope: Scope::Page,
+            pages: BTreeMap::new(),
+            pages_search: None,
+            log: VecDeque::new(),
+            saving: true,
+            exact_sizes: false,
+            ephemeral: false,
+            scrollbar: true,
+            seeder_colors: (1, 10),
+            relative_dates: false,
+            keymap: keymap::KeyMap::default(),
+            theme: theme::Theme::default(),
+            table_rows: Rect::default(),
+            table_window: (0, 0),
+            last_click: None,
+            live_search: false,
+            before_search: None,
+            prefetch: None,
+            confirm_quit: false,
+            export_dir: None,
+            offline: None,
+            fuzzy: String::new(),
         }
     }
 
-    pub fn set_id(&mut self, id: u64) -> std::io::Result<()> {
-        self.last_id = id;
-        // now we have to write the file
-        let home_dir = dirs::home_dir();
-        if let Some(home) = home_dir {
-            let mut nyaa_file = File::options()
-                .create(true)
-                .write(true)
-                .open(&format!("{}/.nyaa", home.display()))?;
-            nyaa_file.write_all(format!("{}", self.last_id).as_bytes())?;
-        };
+    // the fly.dev backend can answer with nothing while it cold-starts
+    pub fn should_retry_empty(&self, params: &Params, items: &Responses) -> bool {
+        self.retry_empty && !self.fetched && items.is_empty() && !params.query.is_empty()
+    }
 
-        Ok(())
+    // whether an external program can be launched now, starting the cooldown if so
+    pub fn may_launch(&mut self, now: Instant) -> bool {
+        if let Some(launched) = self.launched {
+            if now.saturating_duration_since(launched) < self.launch_cooldown {
+                return false;
+            }
+        }
+        self.launched = Some(now);
+        true
     }
 
-    pub fn update_items(&mut self, items: Responses) {
-        self.items = items;
+    pub fn log<S: Into<String>>(&mut self, entry: S) {
+        self.log.push_back((unix_now(), entry.into()));
+        if self.log.len() > LOG_LEN {
+            self.log.pop_front();
+        }
     }
 
-    pub fn first_item(&mut self) {
-        self.current = Some(0);
-        self.state.select(Some(0))
+    // the command m runs for item, the most specific category match first
+    pub fn magnet_command_for(&self, item: &Response) -> Option<&str> {
+        let sub = format!("{}/{}", item.category, item.sub_category);
+        let find = |name: &str| {
+            self.category_commands
+                .iter()
+                .find(|(category, _)| category.eq_ignore_ascii_case(name))
+                .map(|(_, command)| command.as_str())
+        };
+        find(&sub)
+            .or_else(|| find(&item.category))
+            .or(self.magnet_command.as_deref())
     }
 
-    pub fn last_item(&mut self) {
-        let last = Some(self.items.len() - 1);
-        self.current = last;
-        self.state.select(last);
+    pub fn is_marked(&self, id: &str) -> bool {
+        self.marked.iter().any(|i| i.id == id)
     }
 
-    pub fn next_by(&mut self, amount: usize) {
-        let i = match self.state.selected() {
+    // mark or unmark the selected item
+    pub fn toggle_mark(&mut self) {
+        let item = match self.current.and_then(|i| self.items.get(i)) {
+            Some(item) => item,
+            None => return,
+        };
+        match self.marked.iter().position(|i| i.id == item.id) {
             Some(i) => {
-                if i + amount >= self.items.len() - 1 {
-                    self.items.len() - 1
-                } else {
-                    i + amount
-                }
+                self.marked.remove(i);
             }
-            None => 0,
-        };
-        self.current = Some(i);
-        self.state.select(Some(i));
+            None => self.marked.push(item.clone()),
+        }
     }
 
-    pub fn previous_by(&mut self, amount: usize) {
-        let i = match self.state.selected() {
+    pub fn is_bookmarked(&self, id: &str) -> bool {
+        self.bookmarks.iter().any(|i| i.id == id)
+    }
+
+    // star or unstar the selected item, saving the bookmarks either way
+    pub fn toggle_bookmark(&mut self) {
+        let item = match self.selected() {
+            Some(item) => item.clone(),
+            None => return,
+        };
+        match self.bookmarks.iter().position(|i| i.id == item.id) {
             Some(i) => {
-                if amount >= i {
-                    0
-                } else {
-                    i - amount
-                }
+                self.bookmarks.remove(i);
             }
-            None => 0,
+            None => self.bookmarks.push(item),
+        }
+        let bookmarks = self.bookmarks.clone();
+        self.persist(|| save_bookmarks(&bookmarks));
+        if self.show_bookmarks {
+            self.rebuild(self.selected_id());
+        }
+    }
+
+    pub fn toggle_bookmarks_view(&mut self) {
+        self.show_bookmarks = !self.show_bookmarks;
+        self.rebuild(self.selected_id());
+    }
+
+    // what s acts on: the marked items if there are any, else the selected one
+    pub fn viewed_targets(&self) -> Vec<u64> {
+        let items = if self.marked.is_empty() {
+            self.selected().into_iter().collect::<Vec<_>>()
+        } else {
+            self.marked.iter().collect()
         };
-        self.current = Some(i);
-        self.state.select(Some(i));
+        items
+            .into_iter()
+            .filter_map(|item| item.id.parse::<u64>().ok())
+            .collect()
     }
-}
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Response {
-    pub id: String,
-    pub name: String,
-    pub hash: String,
-    pub date: String,
-    pub filesize: String,
-    pub category: String,
-    pub sub_category: String,
-    pub magnet: String,
-    pub torrent: String,
-    pub seeders: String,
-    pub leechers: String,
-    pub completed: String,
-    pub status: String,
-}
+    fn is_viewed_id(&self, id: u64) -> bool {
+        self.viewed.get(&id).copied().unwrap_or(id <= self.last_id)
+    }
 
-type Responses = Vec<Response>;
+    // s marks the targets viewed, or unviewed if they all already are
+    pub fn viewed_flip(&self, ids: &[u64]) -> bool {
+        !ids.iter().all(|id| self.is_viewed_id(*id))
+    }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let mut app = App::new();
-    app.set_id(get_last_id().unwrap());
-    let mut params = Params::new();
-    let items = get_items(&params).await?;
-    app.update_items(items);
+    // how many of ids would change if they were all set to viewed
+    pub fn marking_affects(&self, ids: &[u64], viewed: bool) -> usize {
+        ids.iter()
+            .filter(|id| self.is_viewed_id(**id) != viewed)
+            .count()
+    }
 
-    // setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    pub fn set_viewed(&mut self, ids: &[u64], viewed: bool) {
+        for id in ids {
+            self.viewed.insert(*id, viewed);
+            if !self.ephemeral {
+                self.saved.viewed.insert(*id, viewed);
+            }
+        }
+        if !self.ephemeral {
+            let saved = self.saved.clone();
+            self.persist(|| save_state(&saved));
+        }
+    }
 
-    // create app and run it
-    run_app(&mut terminal, app, &mut params).await?;
+    // remember the query for next time, if it changed
+    pub fn save_query(&mut self, query: &str) {
+        if self.saved.last_query != query {
+            self.saved.last_query = query.to_string();
+            let saved = self.saved.clone();
+            self.persist(|| save_state(&saved));
+        }
+    }
 
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    // put a typed search at the top of the history, dropping an older copy
+    pub fn remember_search(&mut self, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        let history = &mut self.saved.history;
+        history.retain(|q| q != query);
+        history.insert(0, query.to_string());
+        history.truncate(HISTORY_LEN);
+        let saved = self.saved.clone();
+        self.persist(|| save_state(&saved));
+    }
 
-    Ok(())
-}
+    // write something to disk. the first failure turns saving off for the
+    // session, so a read-only home costs one warning rather than one per action
+    pub fn persist<F: FnOnce() -> io::Result<()>>(&mut self, write: F) {
+        if !self.saving {
+            return;
+        }
+        if let Err(e) = write() {
+            self.saving = false;
+            self.log(format!(
+                "Persistence disabled, couldn't write to the home directory: {}",
+                e
+            ));
+        }
+    }
 
-// fetch the request
-async fn get_items(params: &Params) -> Result<Responses, Box<dyn Error>> {
-    let client = reqwest::Client::new();
-    let query = client
-        .get(NYAA_URL)
-        .query(&[("p", params.page.to_string()), ("q", params.query.clone())]);
-    let res = query.send().await?.json::<Responses>().await?;
+    // the columns currently on screen, in order
+    pub fn columns(&self) -> Vec<&'static Column> {
+        COLUMNS
+            .iter()
+            .filter(|c| !c.optional || self.extra_columns.contains(&c.kind))
+            .collect()
+    }
 
-    Ok(res)
-}
+    pub fn toggle_column(&mut self, kind: ColumnKind) {
+        match self.extra_columns.iter().position(|k| *k == kind) {
+            Some(i) => {
+                self.extra_columns.remove(i);
+            }
+            None => self.extra_columns.push(kind),
+        }
+    }
 
-async fn run_app<B: Backend>(
-    terminal: &mut Terminal<B>,
-    mut app: App,
-    params: &mut Params,
-) -> Result<(), Box<dyn Error>> {
-    let mut amount = String::from("");
-    loop {
-        terminal.draw(|f| ui(f, &mut app))?;
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('9') => amount.push('9'),
-                KeyCode::Char('8') => amount.push('8'),
-                KeyCode::Char('7') => amount.push('7'),
-                KeyCode::Char('6') => amount.push('6'),
-                KeyCode::Char('5') => amount.push('5'),
-                KeyCode::Char('4') => amount.push('4'),
-                KeyCode::Char('3') => amount.push('3'),
-                KeyCode::Char('2') => amount.push('2'),
-                KeyCode::Char('1') => amount.push('1'),
-                KeyCode::Char('0') => amount.push('0'),
-                KeyCode::Char('q') => return Ok(()),
-                KeyCode::Down | KeyCode::Char('j') => {
-                    app.next_by(amount.parse::<usize>().unwrap_or(1));
-                    amount = String::default();
-                }
-                KeyCode::Up | KeyCode::Char('k') => {
-                    app.previous_by(amount.parse::<usize>().unwrap_or(1));
-                    amount = String::default();
-                }
-                KeyCode::Char('G') => app.last_item(),
-                KeyCode::Char('g') => app.first_item(),
-                KeyCode::Char('n') => {
-                    params.next_page_by(amount.parse::<u16>().unwrap_or(1));
-                    let items = get_items(params).await?;
-                    app.update_items(items);
-                    terminal.draw(|f| ui(f, &mut app))?;
-                }
-                KeyCode::Char('p') => {
-                    params.prev_page_by(amount.parse::<u16>().unwrap_or(1));
-                    let items = get_items(params).await?;
-                    app.update_items(items);
-                    terminal.draw(|f| ui(f, &mut app))?;
-                }
-                KeyCode::Char('/') => {
-                    let mut query = String::from("");
-                    loop {
-                        if let Event::Key(key) = event::read()? {
-                            match key.code {
-                                KeyCode::Char(c) => query.push(c),
-                                KeyCode::Enter => break,
-                                KeyCode::Backspace => {
-                                    query.pop();
-                                }
-                                _ => {}
-                            }
-                        }
-                        terminal.draw(|f| search_ui(f, &query))?;
-                    }
-                    params.set_query(query);
-                    let items = get_items(params).await?;
-                    app.update_items(items);
-                    terminal.draw(|f| ui(f, &mut app))?;
-                }
-                KeyCode::Char('o') => {
-                    open_url(&format!(
-                        "https://nyaa.si/view/{}",
-                        app.items[app.current.unwrap_or(0)].id
-                    ));
-                }
-                KeyCode::Char('m') => {
-                    open_url(&app.items[app.current.unwrap_or(0)].magnet.to_string());
-                }
-                KeyCode::Char('t') => {
-                    open_url(&app.items[app.current.unwrap_or(0)].torrent.to_string());
-                }
-                KeyCode::Char('b') => {
-                    params.set_query("");
-                    let items = get_items(params).await?;
-                    app.update_items(items);
-                    terminal.draw(|f| ui(f, &mut app))?;
+    // remember the seeder counts so the trend column has something to draw
+    fn record_trend(&mut self, items: &Responses) {
+        for item in items {
+            let history = self.trend.entry(item.id.clone()).or_default();
+            history.push(parse_count(&item.seeders).unwrap_or(0));
+            if history.len() > TREND_LEN {
+                history.remove(0);
+            }
+        }
+    }
+
+    pub fn update_items(&mut self, items: Responses) {
+        self.record_trend(&items);
+        self.page_items = items;
+        self.rebuild(None);
+    }
+
+    // a freshly fetched page. the selection goes back to the top for a
+    // different page or a shorter list, otherwise it stays where it was
+    pub fn show_page(&mut self, items: Responses, moved: bool) {
+        let shorter = items.len() < self.page_items.len();
+        self.update_items(items);
+        if moved || shorter {
+            self.first_item();
+        }
+    }
+
+    // start fetching the page after this one if the selection is near the end.
+    // only once the api has answered, so a dead backend isn't asked twice
+    pub fn prefetch(&mut self, params: &Params) {
+        let near_end = match self.current {
+            Some(i) => i + PREFETCH_MARGIN >= self.items.len(),
+            None => false,
+        };
+        if !self.warm || self.show_bookmarks || !near_end || params.page >= nyaa::MAX_PAGE {
+            return;
+        }
+        let mut next = params.clone();
+        next.next_page_by(1);
+        if matches!(&self.prefetch, Some((fetching, ..)) if *fetching == next) {
+            return;
+        }
+        self.drop_prefetch();
+        // its retries aren't the user's fetch failing, so they stay off the screen
+        let client = self.client.clone().background();
+        let search = next.clone();
+        let task = tokio::spawn(async move { client.search(&search).await.map(|r| r.items) });
+        self.prefetch = Some((next, Instant::now(), task));
+    }
+
+    // the prefetched page if it's the one asked for and recent enough. any
+    // other is stale and dropped
+    pub fn take_prefetch(&mut self, params: &Params, now: Instant) -> Option<PageTask> {
+        match self.prefetch.take() {
+            Some((fetched, started, task))
+                if fetched == *params
+                    && now.saturating_duration_since(started) < PREFETCH_MAX_AGE =>
+            {
+                Some(task)
+            }
+            other => {
+                self.prefetch = other;
+                self.drop_prefetch();
+                None
+            }
+        }
+    }
+
+    fn drop_prefetch(&mut self) {
+        if let Some((_, _, task)) = self.prefetch.take() {
+            task.abort();
+        }
+    }
+
+    // put up the cached page after the first fetch failed
+    pub fn show_cache(&mut self, params: &mut Params, cache: Cache) {
+        params.set_query(cache.query);
+        params.page = cache.page.clamp(1, nyaa::MAX_PAGE);
+        self.offline = Some(cache.fetched);
+        self.update_items(cache.items);
+        self.first_item();
+    }
+
+    // esc out of a live search: back to what was up before / was pressed
+    pub fn cancel_live_search(&mut self, params: &mut Params) {
+        if let Some((previous, items)) = self.before_search.take() {
+            *params = previous;
+            self.save_query(&params.query);
+            self.remember_page(params, &items);
+            self.page_items = items;
+            self.rebuild(None);
+        }
+    }
+
+    // an explicit sort (--sort, ,<n>, r, a saved search) wins over the default
+    fn resort(&mut self, prev_id: Option<String>) {
+        match self.sort.or(self.default_sort) {
+            Some((key, ascending)) => self.sort_by(key, ascending),
+            None => self.reconcile_selection(prev_id),
+        }
+    }
+
+    // keep a fetched page for Scope::All, forgetting the others once the search changes
+    pub fn remember_page(&mut self, params: &Params, items: &Responses) {
+        let search = (
+            params.query.clone(),
+            params.category.clone(),
+            params.sub_category.clone(),
+            params.filter,
+        );
+        if self.pages_search.as_ref() != Some(&search) {
+            self.pages.clear();
+            self.pages_search = Some(search);
+        }
+        self.pages.insert(params.page, items.clone());
+    }
+
+    // page itself, or every fetched page in page order without repeats
+    fn scoped(&self, page: Responses) -> Responses {
+        if self.scope == Scope::Page || self.pages.is_empty() {
+            return page;
+        }
+        let mut seen = HashSet::new();
+        self.pages
+            .values()
+            .flatten()
+            .filter(|item| seen.insert(item.id.clone()))
+            .cloned()
+            .collect()
+    }
+
+    pub fn toggle_scope(&mut self) {
+        self.scope = match self.scope {
+            Scope::Page => Scope::All,
+            Scope::All => Scope::Page,
+        };
+        self.rebuild(self.selected_id());
+    }
+
+    pub fn set_fuzzy(&mut self, pattern: &str) {
+        self.fuzzy = pattern.to_string();
+        self.rebuild(self.selected_id());
+    }
+
+    pub fn set_min_seeders(&mut self, min: Option<u32>) {
+        self.min_seeders = min.filter(|min| *min > 0);
+        self.rebuild(self.selected_id());
+    }
+
+    // the rows on screen: this page or every fetched one, less the filtered out
+    fn rebuild(&mut self, prev_id: Option<String>) {
+        let mut items = if self.show_bookmarks {
+            self.bookmarks.clone()
+        } else {
+            self.scoped(self.page_items.clone())
+        };
+        if let Some(min) = self.min_seeders {
+            // unreadable counts can't show a healthy swarm either
+            items.retain(|item| parse_count(&item.seeders).is_some_and(|n| n >= u64::from(min)));
+        }
+        if !self.fuzzy.is_empty() {
+            items.retain(|item| fuzzy_match(&item.name, &self.fuzzy).is_some());
+        }
+        self.items = items;
+        self.resort(prev_id);
+    }
+
+    // the item actions apply to, the first one if nothing was picked yet
+    pub fn selected(&self) -> Option<&Response> {
+        self.items.get(self.current.unwrap_or(0))
+    }
+
+    pub fn selected_id(&self) -> Option<String> {
+        let item = self.items.get(self.current?)?;
+        Some(item.id.clone())
+    }
+
+    // after items were filtered or reordered, select prev_id wherever it went,
+    // or clamp the old index if it's gone
+    pub fn reconcile_selection(&mut self, prev_id: Option<String>) {
+        let moved = prev_id.and_then(|id| self.items.iter().position(|i| i.id == id));
+        let i = match (moved, self.current) {
+            (Some(i), _) => Some(i),
+            (None, _) if self.items.is_empty() => None,
+            (None, Some(i)) => Some(i.min(self.items.len() - 1)),
+            (None, None) => None,
+        };
+        self.current = i;
+        self.state.select(i);
+    }
+
+    pub fn sort_by(&mut self, key: SortKey, ascending: bool) {
+        let selected = self.selected_id();
+        self.sort = Some((key, ascending));
+        self.items.sort_by(|a, b| {
+            // unknown sizes stay at the bottom whichever way the sort goes
+            if key == SortKey::Size {
+                match (parse_filesize(&a.filesize), parse_filesize(&b.filesize)) {
+                    (None, Some(_)) => return std::cmp::Ordering::Greater,
+                    (Some(_), None) => return std::cmp::Ordering::Less,
+                    _ => {}
                 }
-                KeyCode::Char('h') => loop {
-                    terminal.draw(|f| popup_ui(f))?;
-                    if let Event::Key(_) = event::read()? {
-                        break;
-                    }
-                },
-                KeyCode::Char('s') => {
-                    let id = app.items[app.current.unwrap_or(0)]
-                        .id
-                        .parse::<u64>()
-                        .unwrap_or(0);
-                    app.set_id(id);
+            }
+            let ordering = key.compare(a, b);
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+        self.reconcile_selection(selected);
+    }
+
+    // sort by the next key, in its usual direction
+    pub fn cycle_sort(&mut self) {
+        let key = match self.sort.or(self.default_sort) {
+            Some((key, _)) => key.next(),
+            None => SortKey::ALL[0],
+        };
+        self.sort_by(key, key.default_ascending());
+    }
+
+    // flip whatever order is on screen, keeping the same item selected
+    pub fn reverse(&mut self) {
+        let selected = self.selected_id();
+        self.items.reverse();
+        if let Some((key, ascending)) = self.sort {
+            self.sort = Some((key, !ascending));
+        }
+        self.reconcile_selection(selected);
+    }
+
+    // save the current view under a name, replacing any search with that name
+    pub fn save_search(&mut self, name: &str, params: &Params) {
+        let search = SavedSearch {
+            name: name.to_string(),
+            query: params.query.clone(),
+            category: params.category.clone(),
+            sub_category: params.sub_category.clone(),
+            sort: self.sort,
+            filter: params.filter,
+        };
+        match self.searches.iter_mut().find(|s| s.name == name) {
+            Some(existing) => *existing = search,
+            None => self.searches.push(search),
+        }
+    }
+
+    // sort by the nth column, flipping the direction if it's already the sort
+    pub fn sort_by_column(&mut self, column: usize) {
+        let key = match self.columns().get(column).and_then(|c| c.sort) {
+            Some(key) => key,
+            None => return,
+        };
+        let ascending = match self.sort {
+            Some((current, ascending)) if current == key => !ascending,
+            _ => key.default_ascending(),
+        };
+        self.sort_by(key, ascending);
+    }
+
+    pub fn is_viewed(&self, item: &Response) -> bool {
+        item.id.parse::<u64>().is_ok_and(|id| self.is_viewed_id(id))
+    }
+
+    // select the first item s hasn't covered yet, false if there isn't one
+    pub fn first_unviewed(&mut self) -> bool {
+        match self.items.iter().position(|item| !self.is_viewed(item)) {
+            Some(i) => {
+                self.current = Some(i);
+                self.state.select(Some(i));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // select the next item s hasn't covered after the selected one, going
+    // round to the top past the end. false if there isn't one
+    pub fn next_unviewed(&mut self) -> bool {
+        let len = self.items.len();
+        let start = self.current.map_or(0, |i| i + 1);
+        let found = (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|i| !self.is_viewed(&self.items[*i]));
+        match found {
+            Some(i) => {
+                self.current = Some(i);
+                self.state.select(Some(i));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // the item drawn at a screen cell, None for the header, borders and the
+    // blank line between rows
+    pub fn row_at(&self, x: u16, y: u16) -> Option<usize> {
+        let area = self.table_rows;
+        if x < area.x || x >= area.right() || y < area.y || y >= area.bottom() {
+            return None;
+        }
+        let line = y - area.y;
+        if line % (ROW_HEIGHT + 1) >= ROW_HEIGHT {
+            return None;
+        }
+        let (start, end) = self.table_window;
+        let i = start + usize::from(line / (ROW_HEIGHT + 1));
+        (i < end.min(self.items.len())).then_some(i)
+    }
+
+    // select item i, or the last one if there aren't that many
+    pub fn select_index(&mut self, i: usize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = i.min(self.items.len() - 1);
+        self.current = Some(i);
+        self.state.select(Some(i));
+    }
+
+    pub fn first_item(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        self.current = Some(0);
+        self.state.select(Some(0))
+    }
+
+    pub fn last_item(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let last = Some(self.items.len() - 1);
+        self.current = last;
+        self.state.select(last);
+    }
+
+    pub fn next_by(&mut self, amount: usize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i + amount >= self.items.len() - 1 {
+                    self.items.len() - 1
+                } else {
+                    i + amount
                 }
-                _ => {}
             }
+            None => 0,
+        };
+        self.current = Some(i);
+        self.state.select(Some(i));
+    }
+
+    pub fn previous_by(&mut self, amount: usize) {
+        if self.items.is_empty() {
+            return;
         }
+        let i = match self.state.selected() {
+            Some(i) => i.saturating_sub(amount),
+            None => 0,
+        };
+        self.current = Some(i);
+        self.state.select(Some(i));
     }
 }
 
-fn search_ui<B: Backend>(f: &mut Frame<B>, text: &str) {
-    let size = f.size();
+// HH:MM:SS of a unix time, in UTC
+fn clock(secs: u64) -> String {
+    let secs = secs % 86400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
 
-    let chunks = Layout::default()
-        .constraints([Constraint::Percentage(20)].as_ref())
-        .split(size);
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
-    let paragraph = Paragraph::new(Span::styled(text, Style::default()))
-        .alignment(Alignment::Center)
-        .wrap(Wrap { trim: true });
-    f.render_widget(paragraph, chunks[0]);
+// days since 1970-01-01 for a proleptic gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
-    let rects = Layout::default()
-        .constraints([Constraint::Percentage(100)].as_ref())
-        .margin(1)
-        .split(f.size());
+// parse the api's dates into unix seconds, treating them as utc. understands
+// "2022-10-15 12:34[:56]", "Sat, 15 Oct 2022 12:34:56 -0000" and timestamps
+fn parse_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
 
-    let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-    let normal_style = Style::default().bg(Color::Blue);
-    let header_cells = ["Viewed", "Name", "Date", "Size", "Seeders", "Leechers"]
-        .iter()
-        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Red)));
-    let header = Row::new(header_cells)
-        .style(normal_style)
-        .height(1)
-        .bottom_margin(1);
-    let rows = app.items.iter().map(|item| {
-        let Response {
-            id,
-            date,
-            name,
-            filesize,
-            seeders,
-            leechers,
-            ..
-        } = item;
-        let height = 3;
-        let viewed = if id.parse::<u64>().unwrap() <= app.last_id {
-            "✅"
-        } else {
-            "❌"
-        };
-        let cells = [viewed, name, date, filesize, seeders, leechers]
-            .map(|x| x.to_string())
-            .map(Cell::from);
-        Row::new(cells).height(height as u16).bottom_margin(1)
-    });
-    let t = Table::new(rows)
-        .header(header)
-        .block(Block::default().borders(Borders::ALL).title("Table"))
-        .highlight_style(selected_style)
-        .highlight_symbol(">> ")
-        .widths(&[
-            Constraint::Percentage(2),
-            Constraint::Percentage(70),
-            Constraint::Percentage(9),
-            Constraint::Percentage(8),
-            Constraint::Percentage(5),
-            Constraint::Percentage(5),
-        ]);
-    f.render_stateful_widget(t, rects[0], &mut app.state);
+    let date = date.trim();
+    if !date.is_empty() && date.chars().all(|c| c.is_ascii_digit()) {
+        return date.parse().ok();
+    }
+
+    let tokens: Vec<&str> = date
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let number = |i: usize| tokens.get(i).and_then(|t| t.parse::<i64>().ok());
+    let (year, month, day, rest) = if tokens.first()?.len() == 4 {
+        (number(0)?, number(1)?, number(2)?, 3)
+    } else {
+        let month = tokens
+            .iter()
+            .position(|t| MONTHS.contains(&t.get(..3).unwrap_or("").to_lowercase().as_str()))?;
+        let name = tokens[month][..3].to_lowercase();
+        let index = MONTHS.iter().position(|m| *m == name)? as i64 + 1;
+        (
+            number(month + 1)?,
+            index,
+            number(month.checked_sub(1)?)?,
+            month + 2,
+        )
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year > 9999 {
+        return None;
+    }
+    let hour = number(rest).unwrap_or(0);
+    let minute = number(rest + 1).unwrap_or(0);
+    if hour > 23 || minute > 59 {
+        return None;
+    }
+    let second = number(rest + 2).filter(|s| *s < 60).unwrap_or(0);
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    u64::try_from(seconds).ok()
 }
 
-fn popup_ui<B: Backend>(f: &mut Frame<B>) {
-    let size = f.size();
+// how long ago date was, like "3d ago", or date as it came if it won't parse
+fn relative_date(date: &str, now: u64) -> String {
+    match parse_date(date) {
+        Some(then) => ago(now.saturating_sub(then)),
+        None => date.to_string(),
+    }
+}
 
-    const HELP_TEXT: &str = "
-/ to search
-s to mark the current spot as viewed until
-<number> n to go to the next page (like 5n to go 5 more pages)
-<number> p to go to the prev page (like 5p to go 5 fewer pages)
-<number> j or down arrow to go down one item.
-<number> k or up arrow to up one item.
-o to open the selected item in the web browser.
-m to open up the selected item's magnet link.
-t to open up the selected item's torrent link.
-";
-    let paragraph = Paragraph::new(Span::from(HELP_TEXT))
-        .block(Block::default().borders(Borders::ALL))
-        .alignment(Alignment::Left)
-        .wrap(Wrap { trim: true });
-    f.render_widget(paragraph, size);
+// an age in seconds in its largest unit, like "3d ago"
+fn ago(age: u64) -> String {
+    const UNITS: [(u64, &str); 5] = [
+        (365 * 86400, "y"),
+        (30 * 86400, "mo"),
+        (86400, "d"),
+        (3600, "h"),
+        (60, "m"),
+    ];
+    match UNITS.iter().find(|(secs, _)| age >= *secs) {
+        Some((secs, unit)) => format!("{}{} ago", age / secs, unit),
+        None => "just now".to_string(),
+    }
+}
+
+// green for the last day, yellow for the last week, nothing after that
+fn freshness(date: &str, now: u64) -> Option<Color> {
+    let age = now.saturating_sub(parse_date(date)?);
+    if age < 24 * 3600 {
+        Some(Color::Green)
+    } else if age < 7 * 24 * 3600 {
+        Some(Color::Yellow)
+    } else {
+        None
+    }
+}
+
+// how nyaa colors its rows: green for trusted uploads, red for remakes
+fn status_color(status: &str) -> Option<Color> {
+    match status.trim().to_ascii_lowercase().as_str() {
+        "trusted" => Some(Color::Green),
+        "remake" => Some(Color::Red),
+        _ => None,
+    }
+}
+
+// a named query and the view it was saved with, kept in ~/.nyaa_searches.json
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct SavedSearch {
+    name: String,
+    query: String,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    sub_category: Option<String>,
+    #[serde(default)]
+    sort: Option<(SortKey, bool)>,
+    #[serde(default)]
+    filter: u8,
+}
+
+impl SavedSearch {
+    fn describe(&self) -> String {
+        let mut line = format!("{}: \"{}\"", self.name, self.query);
+        let category = category_label(self.category.as_deref(), self.sub_category.as_deref());
+        if let Some(category) = category {
+            line.push_str(&format!(" in {}", category));
+        }
+        if let Some(label) = nyaa::filter_label(self.filter) {
+            line.push_str(&format!(", {}", label));
+        }
+        if let Some((key, ascending)) = self.sort {
+            let direction = if ascending { "↑" } else { "↓" };
+            line.push_str(&format!(" by {} {}", key.name(), direction));
+        }
+        line
+    }
+
+    fn apply(&self, app: &mut App, params: &mut Params) {
+        params.set_query(&self.query);
+        params.set_category(self.category.as_deref(), self.sub_category.as_deref());
+        params.page = 1;
+        params.filter = self.filter;
+        app.sort = self.sort;
+    }
+}
+
+fn searches_path() -> Option<std::path::PathBuf> {
+    home_dir().map(|home| home.join(".nyaa_searches.json"))
+}
+
+fn load_searches() -> Vec<SavedSearch> {
+    searches_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_searches(searches: &[SavedSearch]) -> io::Result<()> {
+    if let Some(path) = searches_path() {
+        std::fs::write(path, serde_json::to_string_pretty(searches)?)?;
+    }
+    Ok(())
+}
+
+// the last page that came back with results, in ~/.nyaa_cache.json, for
+// browsing when the api can't be reached at startup
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Cache {
+    query: String,
+    page: u16,
+    // unix time of the fetch
+    fetched: u64,
+    items: Responses,
+}
+
+fn cache_path() -> Option<std::path::PathBuf> {
+    home_dir().map(|home| home.join(".nyaa_cache.json"))
+}
+
+fn load_cache(path: &std::path::Path) -> Option<Cache> {
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+fn save_cache(path: &std::path::Path, cache: &Cache) -> io::Result<()> {
+    std::fs::write(path, serde_json::to_string(cache)?)
+}
+
+fn bookmarks_path() -> Option<std::path::PathBuf> {
+    home_dir().map(|home| home.join(".nyaa_bookmarks.json"))
+}
+
+fn load_bookmarks() -> Responses {
+    bookmarks_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_bookmarks(bookmarks: &[Response]) -> io::Result<()> {
+    if let Some(path) = bookmarks_path() {
+        std::fs::write(path, serde_json::to_string_pretty(bookmarks)?)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    Name,
+    Date,
+    Size,
+    Seeders,
+    Leechers,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 5] = [
+        SortKey::Name,
+        SortKey::Date,
+        SortKey::Size,
+        SortKey::Seeders,
+        SortKey::Leechers,
+    ];
+
+    fn compare(&self, a: &Response, b: &Response) -> std::cmp::Ordering {
+        let count = |s: &str| parse_count(s).unwrap_or(0);
+        match self {
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            // ids are handed out in upload order, so they sort like dates
+            SortKey::Date => count(&a.id).cmp(&count(&b.id)),
+            SortKey::Size => parse_filesize(&a.filesize).cmp(&parse_filesize(&b.filesize)),
+            SortKey::Seeders => count(&a.seeders).cmp(&count(&b.seeders)),
+            SortKey::Leechers => count(&a.leechers).cmp(&count(&b.leechers)),
+        }
+    }
+
+    // names read best a-z, everything else biggest/newest first
+    fn default_ascending(&self) -> bool {
+        matches!(self, SortKey::Name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Date => "date",
+            SortKey::Size => "size",
+            SortKey::Seeders => "seeders",
+            SortKey::Leechers => "leechers",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<SortKey> {
+        SortKey::ALL.into_iter().find(|key| key.name() == name)
+    }
+
+    // the key after this one in ALL, wrapping around
+    fn next(&self) -> SortKey {
+        let i = SortKey::ALL.iter().position(|key| key == self).unwrap_or(0);
+        SortKey::ALL[(i + 1) % SortKey::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ColumnKind {
+    Viewed,
+    Name,
+    Date,
+    Size,
+    Seeders,
+    Leechers,
+    Bookmark,
+    Status,
+    Trend,
+    Hash,
+    Category,
+}
+
+struct Column {
+    kind: ColumnKind,
+    title: &'static str,
+    // percent of the table, the name column takes whatever is left over
+    width: u16,
+    // None for columns where an ordering wouldn't mean anything
+    sort: Option<SortKey>,
+    // hidden until toggled on
+    optional: bool,
+}
+
+const COLUMNS: [Column; 11] = [
+    Column {
+        kind: ColumnKind::Viewed,
+        title: "Viewed",
+        width: 2,
+        sort: None,
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Name,
+        title: "Name",
+        width: 0,
+        sort: Some(SortKey::Name),
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Date,
+        title: "Date",
+        width: 9,
+        sort: Some(SortKey::Date),
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Size,
+        title: "Size",
+        width: 8,
+        sort: Some(SortKey::Size),
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Seeders,
+        title: "Seeders",
+        width: 5,
+        sort: Some(SortKey::Seeders),
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Leechers,
+        title: "Leechers",
+        width: 5,
+        sort: Some(SortKey::Leechers),
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Bookmark,
+        title: "Fav",
+        width: 3,
+        sort: None,
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Status,
+        title: "",
+        width: 2,
+        sort: None,
+        optional: false,
+    },
+    Column {
+        kind: ColumnKind::Trend,
+        title: "Trend",
+        width: 8,
+        sort: None,
+        optional: true,
+    },
+    Column {
+        kind: ColumnKind::Hash,
+        title: "Hash",
+        width: 7,
+        sort: None,
+        optional: true,
+    },
+    Column {
+        kind: ColumnKind::Category,
+        title: "Category",
+        width: 11,
+        sort: None,
+        optional: true,
+    },
+];
+
+// lines a table row takes, plus one blank line under it
+const ROW_HEIGHT: u16 = 3;
+
+// the rows tui's Table will draw, as (first, past the last), given where it
+// started last frame. the same walk as its get_row_bounds, which keeps the
+// offset to itself, with every row ROW_HEIGHT tall
+fn row_window(
+    offset: usize,
+    selected: Option<usize>,
+    len: usize,
+    max_height: u16,
+) -> (usize, usize) {
+    if len == 0 {
+        return (0, 0);
+    }
+    let total = ROW_HEIGHT + 1;
+    // a cleared selection puts tui back at the top
+    let offset = if selected.is_none() {
+        0
+    } else {
+        offset.min(len - 1)
+    };
+    let (mut start, mut end) = (offset, offset);
+    let mut height: u16 = 0;
+    while end < len && height + ROW_HEIGHT <= max_height {
+        height += total;
+        end += 1;
+    }
+    let selected = selected.unwrap_or(0).min(len - 1);
+    while selected >= end {
+        height = height.saturating_add(total);
+        end += 1;
+        while height > max_height {
+            height = height.saturating_sub(total);
+            start += 1;
+        }
+    }
+    while selected < start {
+        start -= 1;
+        height = height.saturating_add(total);
+        while height > max_height {
+            end -= 1;
+            height = height.saturating_sub(total);
+        }
+    }
+    (start, end)
+}
+
+// seeder counts seen for an item this session, oldest first
+const TREND_LEN: usize = 8;
+// how many entries the action log keeps
+const LOG_LEN: usize = 200;
+
+// the longer category names, cut down to fit the category column
+const CATEGORY_ABBREVIATIONS: [(&str, &str); 9] = [
+    ("English-translated", "Eng"),
+    ("Non-English-translated", "Non-Eng"),
+    ("Anime Music Video", "AMV"),
+    ("Idol/Promotional Video", "Idol/PV"),
+    ("Literature", "Lit"),
+    ("Live Action", "Live"),
+    ("Pictures", "Pics"),
+    ("Software", "Soft"),
+    ("Applications", "Apps"),
+];
+
+// like "Anime/Eng" for Anime - English-translated
+fn short_category(category: &str, sub_category: &str) -> String {
+    let short = |name: &str| {
+        CATEGORY_ABBREVIATIONS
+            .iter()
+            .find(|(long, _)| long.eq_ignore_ascii_case(name.trim()))
+            .map_or(name.trim().to_string(), |(_, short)| short.to_string())
+    };
+    match (category.trim(), sub_category.trim()) {
+        (category, "") => short(category),
+        ("", sub) => short(sub),
+        (category, sub) => format!("{}/{}", short(category), short(sub)),
+    }
+}
+
+// enough of an info hash to spot the same torrent twice
+fn short_hash(hash: &str) -> String {
+    hash.chars().take(8).collect::<String>().to_lowercase()
+}
+
+// a tiny bar chart of the values, scaled between their min and max
+fn sparkline(values: &[u64], ascii: bool) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    const ASCII: [char; 8] = ['_', '.', ',', '-', '~', '=', '*', '#'];
+
+    let bars = if ascii { ASCII } else { BLOCKS };
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|v| {
+            if max == min {
+                bars[3]
+            } else {
+                bars[((v - min) * 7 / (max - min)) as usize]
+            }
+        })
+        .collect()
+}
+
+// which of height cells are the thumb of a scrollbar over len rows, visible
+// at a time, with the selected row in view
+fn scrollbar(len: usize, visible: usize, selected: usize, height: usize) -> Vec<bool> {
+    if len <= visible || height == 0 {
+        return vec![false; height];
+    }
+    let thumb = (height * visible / len).clamp(1, height);
+    let start = (height - thumb) * selected.min(len - 1) / (len - 1);
+    (0..height)
+        .map(|i| i >= start && i < start + thumb)
+        .collect()
+}
+
+// parse a human size like "350.5 MiB" or "1.2 GB" into bytes. empty, "Unknown"
+// and zero sizes are None, which sorts them after every real size
+fn parse_filesize(s: &str) -> Option<u64> {
+    let (number, unit) = split_size(s)?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" | "bytes" => 1,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        _ => return None,
+    };
+    let bytes = (number * multiplier as f64).round();
+    if bytes >= 1.0 {
+        Some(bytes as u64)
+    } else {
+        None
+    }
+}
+
+// "1.23 GiB" as (1.23, "GiB")
+fn split_size(s: &str) -> Option<(f64, &str)> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ','))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number.replace(',', "").parse::<f64>().ok()?;
+    Some((number, unit.trim()))
+}
+
+// s cut down to width cells, ending in … if anything had to go. wide
+// characters (most of a japanese title) take two cells each
+fn ellipsize(s: &str, width: usize) -> String {
+    use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+    if s.width() <= width {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    if width > 0 {
+        out.push('…');
+    }
+    out
+}
+
+// display_size, shortened like "1.2G" when it won't fit in width cells
+fn fit_size(s: &str, width: usize) -> String {
+    let full = display_size(s);
+    if full.chars().count() <= width || parse_filesize(s).is_none() {
+        return full;
+    }
+    let (number, unit) = match split_size(s) {
+        Some(split) => split,
+        None => return full,
+    };
+    let number = if number < 10.0 {
+        format!("{:.1}", number)
+    } else {
+        format!("{:.0}", number)
+    };
+    let unit = unit.chars().next().unwrap_or('B').to_ascii_uppercase();
+    format!("{}{}", number.trim_end_matches(".0"), unit)
+}
+
+// 1288490188 as "1,288,490,188"
+fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+// bytes in the largest binary unit that keeps them at least 1, like "1.20 GiB"
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+// "—" rather than a misleading "0 B" for sizes the api doesn't know
+fn display_size(s: &str) -> String {
+    match parse_filesize(s) {
+        Some(_) => s.to_string(),
+        None => "—".to_string(),
+    }
+}
+
+const USAGE: &str = "usage: nyaa [--query <query>] [--page <page>] [--json | --magnet-only] [--sort <name|date|size|seeders|leechers>] [--limit <n>] [--bench <runs>] [--import <file>]";
+
+// command line options; --json and --magnet-only skip the tui and print the
+// page to stdout
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    query: Option<String>,
+    page: Option<u16>,
+    json: bool,
+    // print one magnet per line instead of json
+    magnet_only: bool,
+    sort: Option<SortKey>,
+    limit: Option<usize>,
+    // fetch the page this many times and report latencies instead
+    bench: Option<usize>,
+    // show the ids and magnets listed in this file instead of a search
+    import: Option<String>,
+}
+
+impl Args {
+    // whether to print the page and exit instead of starting the tui
+    fn batch(&self) -> bool {
+        self.json || self.magnet_only
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--query" | "-q" => parsed.query = Some(value()?),
+            "--page" => {
+                let page = value()?;
+                parsed.page = Some(page.parse().map_err(|_| format!("bad page: {}", page))?);
+            }
+            "--json" => parsed.json = true,
+            "--magnet-only" => parsed.magnet_only = true,
+            "--sort" => {
+                let sort = value()?;
+                parsed.sort =
+                    Some(SortKey::from_name(&sort).ok_or(format!("bad sort key: {}", sort))?);
+            }
+            "--limit" => {
+                let limit = value()?;
+                parsed.limit = Some(limit.parse().map_err(|_| format!("bad limit: {}", limit))?);
+            }
+            "--import" => parsed.import = Some(value()?),
+            "--bench" => {
+                let runs = value()?;
+                parsed.bench = Some(
+                    runs.parse()
+                        .map_err(|_| format!("bad run count: {}", runs))?,
+                );
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut app = App::new();
+    let config = config::load();
+    app.client = nyaa::Client::with_base(&api_url(&config))
+        .retries(retries(&config))
+        .timeout(timeout(&config))
+        .compression(compression_enabled(&config));
+    nyaa::set_rate_limit(rate_limit(&config));
+    app.download_dir = download_dir(&config);
+    app.download_dir_set = dir_setting(&config, "NYAA_DOWNLOAD_DIR", "download_dir").is_some();
+    app.export_dir = dir_setting(&config, "NYAA_EXPORT_DIR", "export_dir");
+    app.keymap = keymap::KeyMap::from_config(&config);
+    app.theme = theme::Theme::from_config(&config);
+    app.seeder_colors = seeder_colors(&config);
+    app.confirm_quit = confirm_quit_enabled(&config);
+    app.saved = load_state();
+    app.last_id = app.saved.last_id;
+    app.viewed = app.saved.viewed.clone();
+    app.retry_empty = retry_empty_enabled();
+    app.ascii = ascii_enabled();
+    app.searches = load_searches();
+    app.bookmarks = load_bookmarks();
+    app.refresh = refresh_interval();
+    app.magnet_command = command_setting(&config, "NYAA_MAGNET_COMMAND", "magnet_command");
+    app.torrent_command = command_setting(&config, "NYAA_TORRENT_COMMAND", "torrent_command");
+    app.category_commands = category_commands(&config);
+    app.tmux = tmux_enabled();
+    app.confirm_mark = confirm_mark_threshold(&config);
+    app.frame_interval = frame_interval(&config);
+    app.launch_cooldown = launch_cooldown(&config);
+    app.default_sort = default_sort(&config);
+    app.scrollbar = scrollbar_enabled(&config);
+    app.exact_sizes = exact_sizes_enabled();
+    app.live_search = live_search_enabled();
+    let mut params = Params::new();
+    params.set_query(args.query.as_ref().unwrap_or(&app.saved.last_query));
+    if let Some(page) = args.page {
+        params.page = page.clamp(1, nyaa::MAX_PAGE);
+    }
+    if let Some(runs) = args.bench {
+        // retries would hide the failures a benchmark should count
+        let client = app.client.clone().retries(0);
+        println!("{}", bench(&client, &params, runs).await?);
+        return Ok(());
+    }
+    let mut mode = Mode::Normal;
+    match &args.import {
+        Some(path) => {
+            let (items, skipped) = parse_import(&std::fs::read_to_string(path)?);
+            let message = format!(
+                "Imported {} items from {}, skipped {} lines that weren't ids or magnets.",
+                items.len(),
+                path,
+                skipped
+            );
+            if skipped > 0 {
+                mode = Mode::Modal(Modal::Message(message));
+            }
+            app.update_items(items);
+        }
+        // batch modes have no screen to show an error on
+        None if args.batch() => {
+            let items = fetch_items(&mut app, &params).await?;
+            app.update_items(items);
+        }
+        None => {
+            mode = load_page(&mut app, &mut params, Params::new()).await;
+            let cache = cache_path().and_then(|path| load_cache(&path));
+            if let (Mode::Modal(Modal::Error(_)), Some(cache)) = (&mode, cache) {
+                app.show_cache(&mut params, cache);
+            }
+        }
+    }
+    if let Some(key) = args.sort {
+        app.sort_by(key, key.default_ascending());
+    }
+
+    if args.batch() {
+        let mut items = app.items;
+        if let Some(limit) = args.limit {
+            items.truncate(limit);
+        }
+        if args.magnet_only {
+            for item in items.iter().filter(|i| !i.magnet.is_empty()) {
+                println!("{}", item.magnet);
+            }
+        } else {
+            println!("{}", serde_json::to_string_pretty(&items)?);
+        }
+        return Ok(());
+    }
+
+    // setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    // xterm's title stack, so the old title can come back when we're done
+    execute!(
+        stdout,
+        Print("\x1b[22;0t"),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    if !app.ascii {
+        let width = emoji_width_override().or_else(|| probe_emoji_width(&mut stdout));
+        app.ascii = width != Some(2);
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    // create app and run it, putting the terminal back even if it fails
+    let result = run_app(&mut terminal, app, &mut params, mode).await;
+
+    // restore terminal
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        SetTitle(""),
+        Print("\x1b[23;0t")
+    )?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+// min/median/p95/max of some latencies, nearest-rank, None if there are none
+fn latency_summary(mut millis: Vec<f64>) -> Option<[f64; 4]> {
+    if millis.is_empty() {
+        return None;
+    }
+    millis.sort_by(|a, b| a.total_cmp(b));
+    let rank =
+        |p: f64| millis[((p * millis.len() as f64).ceil() as usize).clamp(1, millis.len()) - 1];
+    Some([millis[0], rank(0.5), rank(0.95), millis[millis.len() - 1]])
+}
+
+// time runs fetches of the page over one client, as a line of json
+async fn bench(
+    client: &nyaa::Client,
+    params: &Params,
+    runs: usize,
+) -> Result<String, Box<dyn Error>> {
+    let mut millis = vec![];
+    let mut errors = 0;
+    for _ in 0..runs {
+        // elapsed doesn't count the wait for the rate limiter
+        match client.search(params).await {
+            Ok(result) => millis.push(result.elapsed.as_secs_f64() * 1000.0),
+            Err(_) => errors += 1,
+        }
+    }
+
+    let mut summary = serde_json::json!({
+        "url": client.url(params)?.as_str(),
+        "runs": runs,
+        "errors": errors,
+    });
+    if let Some([min, median, p95, max]) = latency_summary(millis) {
+        summary["min_ms"] = min.into();
+        summary["median_ms"] = median.into();
+        summary["p95_ms"] = p95.into();
+        summary["max_ms"] = max.into();
+    }
+    Ok(summary.to_string())
+}
+
+// fetch, retrying once if the backend looks like it's still cold-starting
+async fn fetch_items(app: &mut App, params: &Params) -> Result<Responses, Box<dyn Error>> {
+    // a failed prefetch is just fetched again
+    let prefetched = match app.take_prefetch(params, Instant::now()) {
+        Some(task) => task.await.ok().and_then(|result| result.ok()),
+        None => None,
+    };
+    let searched = match prefetched {
+        Some(items) => Ok(items),
+        None => app.client.search(params).await.map(|result| result.items),
+    };
+    let retry = matches!(&searched, Ok(items) if app.should_retry_empty(params, items));
+    app.fetched = true;
+    let mut items = searched?;
+    if retry {
+        tokio::time::sleep(COLD_START_DELAY).await;
+        items = app.client.search(params).await?.items;
+    }
+    if !items.is_empty() {
+        app.warm = true;
+    }
+    app.offline = None;
+    app.remember_page(params, &items);
+    app.save_query(&params.query);
+    app.log(format!(
+        "Fetched page {} of \"{}\": {} results",
+        params.page,
+        params.query,
+        items.len()
+    ));
+
+    Ok(items)
+}
+
+// fetch the page params asks for. if that fails the old results stay up,
+// params go back to previous to match them, and the error pops up over them.
+// a page with results becomes the offline cache, here rather than in
+// fetch_items so live search and the batch modes don't rewrite it each time
+async fn load_page(app: &mut App, params: &mut Params, previous: Params) -> Mode {
+    match fetch_items(app, params).await {
+        Ok(items) => {
+            if let Some(path) = cache_path().filter(|_| !items.is_empty()) {
+                let cache = Cache {
+                    query: params.query.clone(),
+                    page: params.page,
+                    fetched: unix_now(),
+                    items: items.clone(),
+                };
+                app.persist(|| save_cache(&path, &cache));
+            }
+            app.show_page(items, *params != previous);
+            Mode::Normal
+        }
+        Err(e) => {
+            *params = previous;
+            Mode::Modal(Modal::Error(e.to_string()))
+        }
+    }
+}
+
+// refetch for the query typed so far. a failure is only logged, so typing
+// carries on over the last results that did load
+async fn live_search(app: &mut App, params: &mut Params, text: &str) {
+    let previous = params.clone();
+    params.set_query(text);
+    match fetch_items(app, params).await {
+        Ok(items) => app.show_page(items, true),
+        Err(e) => {
+            *params = previous;
+            app.log(format!("Live search for \"{}\" failed: {}", text, e));
+        }
+    }
+}
+
+// refetch the page as it is, keeping the same item selected if it's still there
+async fn reload(app: &mut App, params: &mut Params) -> Mode {
+    let id = app.selected_id();
+    let mode = load_page(app, params, params.clone()).await;
+    app.reconcile_selection(id);
+    mode
+}
+
+// what the keys mean right now. every key that isn't a digit leaves
+// PendingCount, so a count can only ever apply to the very next command
+#[derive(Clone, Debug)]
+enum Mode {
+    Normal,
+    // digits typed so far, used as the count for the next command
+    PendingCount(String),
+    // waiting for the column number after ','
+    SortPrefix,
+    // a line being typed into the search bar, and the byte offset of the cursor in it
+    Input(Input, String, usize),
+    Modal(Modal),
+}
+
+// a key typed into an input line: characters go in at the cursor, backspace
+// and delete take out the character before and under it, and the arrows,
+// home and end move it. the cursor stays on a character boundary
+fn edit_line(text: &mut String, cursor: &mut usize, code: KeyCode) {
+    let before = text[..*cursor]
+        .chars()
+        .next_back()
+        .map_or(0, char::len_utf8);
+    let under = text[*cursor..].chars().next().map_or(0, char::len_utf8);
+    match code {
+        KeyCode::Char(c) => {
+            text.insert(*cursor, c);
+            *cursor += c.len_utf8();
+        }
+        KeyCode::Backspace if before > 0 => {
+            *cursor -= before;
+            text.remove(*cursor);
+        }
+        KeyCode::Delete if under > 0 => {
+            text.remove(*cursor);
+        }
+        KeyCode::Left => *cursor -= before,
+        KeyCode::Right => *cursor += under,
+        KeyCode::Home => *cursor = 0,
+        KeyCode::End => *cursor = text.len(),
+        _ => {}
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Input {
+    Search,
+    SaveSearch,
+    MinSeeders,
+    Page,
+    Export,
+    Fuzzy,
+}
+
+impl Input {
+    fn label(&self) -> &'static str {
+        match self {
+            Input::Search => "",
+            Input::SaveSearch => "save search as: ",
+            Input::Export => "export to (enter for a timestamped name): ",
+            Input::Fuzzy => "filter this page: ",
+            Input::MinSeeders => "minimum seeders (0 for any): ",
+            Input::Page => "go to page: ",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Modal {
+    Help,
+    Url(String),
+    // the outcome of an action, dismissed by any key
+    Message(String),
+    // a failed fetch, shown over the results that are still there
+    Error(String),
+    // s would set count of ids to viewed (or unviewed), y to go ahead
+    ConfirmMark {
+        ids: Vec<u64>,
+        viewed: bool,
+        count: usize,
+    },
+    // q with confirm_quit on, y or q again quits
+    ConfirmQuit,
+    Trackers {
+        trackers: Vec<String>,
+        state: ListState,
+        title: String,
+    },
+    Searches(ListState),
+    // the recent searches, enter runs one again
+    History(ListState),
+    // every field of the selected item, j/k move to the next or previous one
+    Detail,
+    // the selected item's whole name, for when the column cuts it off
+    Name(String),
+    // an item's magnet, to check before it's opened (o) or copied (y)
+    Magnet(Box<Response>),
+    // the items marked with space, for a look before acting on them
+    Marked {
+        state: ListState,
+        title: String,
+    },
+    // category_choices, picked with enter
+    CategoryList(ListState),
+    // the action log, scrolled with j/k
+    Log(ListState),
+    // the category tree, at the top level or inside CATEGORIES[parent]
+    Categories {
+        parent: Option<usize>,
+        state: ListState,
+    },
+}
+
+const LOG_TITLE: &str = "Log (times in UTC, q to close)";
+const MARKED_TITLE: &str = "Marked (y to copy names, m to copy magnets, c to clear, q to close)";
+
+// a list state pointing at the first line, if there is one
+fn first_selected(len: usize) -> ListState {
+    let mut state = ListState::default();
+    if len > 0 {
+        state.select(Some(0));
+    }
+    state
+}
+
+async fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+    params: &mut Params,
+    mut mode: Mode,
+) -> Result<(), Box<dyn Error>> {
+    let mut refreshed = Instant::now();
+    let mut drawn: Option<Instant> = None;
+    let mut window_title = String::new();
+    // when the live search query last changed, if it hasn't been fetched yet
+    let mut typed: Option<Instant> = None;
+    loop {
+        let title = terminal_title(params);
+        if title != window_title {
+            execute!(io::stdout(), SetTitle(&title))?;
+            window_title = title;
+        }
+        // while keys are queued up, redraw at most once per frame interval
+        let due = drawn.is_none_or(|at| at.elapsed() >= app.frame_interval);
+        if due || !event::poll(Duration::ZERO)? {
+            terminal.draw(|f| draw(f, &mut app, &mut mode, params))?;
+            drawn = Some(Instant::now());
+        }
+        if let (Some(refresh), Mode::Normal) = (app.refresh, &mode) {
+            let wait = refresh.saturating_sub(refreshed.elapsed());
+            if !event::poll(wait)? {
+                mode = with_retry_note(terminal, reload(&mut app, params)).await?;
+                refreshed = Instant::now();
+                continue;
+            }
+        }
+        if let (Some(at), Mode::Input(Input::Search, text, _)) = (typed, &mode) {
+            if !event::poll(LIVE_SEARCH_DELAY.saturating_sub(at.elapsed()))? {
+                let text = text.clone();
+                with_retry_note(terminal, live_search(&mut app, params, &text)).await?;
+                typed = None;
+                continue;
+            }
+        }
+        match event::read()? {
+            Event::Key(key) => {
+                // the bucket may have emptied since the last frame, so the
+                // rate limit note is up before a fetch blocks on it
+                if nyaa::rate_limited() {
+                    terminal.draw(|f| draw(f, &mut app, &mut mode, params))?;
+                }
+                let next = handle_key(mode, key.code, &mut app, params);
+                mode = match with_retry_note(terminal, next).await?? {
+                    Some(mode) => mode,
+                    None => return Ok(()),
+                };
+                typed = match &mode {
+                    Mode::Input(Input::Search, text, _)
+                        if app.live_search && *text != params.query =>
+                    {
+                        Some(Instant::now())
+                    }
+                    _ => None,
+                };
+            }
+            // popups and prompts are keyboard only
+            Event::Mouse(mouse) if matches!(mode, Mode::Normal) => {
+                mode = mouse_event(&mut app, mouse, Instant::now());
+                app.prefetch(params);
+            }
+            _ => {}
+        }
+    }
+}
+
+// two clicks on the same row within this long open its details
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+// a click selects the row under it, a double click opens the details, and
+// the wheel moves the selection
+fn mouse_event(app: &mut App, mouse: MouseEvent, now: Instant) -> Mode {
+    match mouse.kind {
+        MouseEventKind::ScrollDown => app.next_by(1),
+        MouseEventKind::ScrollUp => app.previous_by(1),
+        MouseEventKind::Down(MouseButton::Left) => {
+            if let Some(i) = app.row_at(mouse.column, mouse.row) {
+                let double = matches!(app.last_click, Some((at, last))
+                    if last == i && now.saturating_duration_since(at) < DOUBLE_CLICK);
+                app.select_index(i);
+                if double {
+                    app.last_click = None;
+                    return Mode::Modal(Modal::Detail);
+                }
+                app.last_click = Some((now, i));
+            }
+        }
+        _ => {}
+    }
+    Mode::Normal
+}
+
+// run a key's work, putting a note over the status line while a failed fetch
+// waits to be tried again or a .torrent downloads. the frame underneath is
+// borrowed by the work, so the note is drawn straight to the backend and the
+// next frame redraws it all
+async fn with_retry_note<B: Backend, F: std::future::Future>(
+    terminal: &mut Terminal<B>,
+    work: F,
+) -> io::Result<F::Output> {
+    tokio::pin!(work);
+    let mut noted = 0;
+    let mut ticks = 0;
+    let output = loop {
+        tokio::select! {
+            output = &mut work => break output,
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                if let Some(attempt) = nyaa::retrying().filter(|a| *a != noted) {
+                    note(terminal, &format!("retrying… (retry {})", attempt))?;
+                    noted = attempt;
+                } else if let Some((bytes, size)) = nyaa::download_progress() {
+                    ticks += 1;
+                    note(terminal, &download_note(bytes, size, ticks))?;
+                }
+            }
+        }
+    };
+    if noted > 0 || ticks > 0 {
+        terminal.clear()?;
+    }
+    Ok(output)
+}
+
+// how far a download is, or a spinner and the bytes so far when there's no size
+fn download_note(bytes: u64, size: Option<u64>, tick: usize) -> String {
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+    match size.filter(|size| *size > 0) {
+        Some(size) => format!(
+            "downloading… {} of {} ({}%)",
+            human_size(bytes),
+            human_size(size),
+            bytes.min(size) * 100 / size
+        ),
+        None => format!(
+            "{} downloading… {}",
+            SPINNER[tick % SPINNER.len()],
+            human_size(bytes)
+        ),
+    }
+}
+
+// padded so a shorter note covers the one before it
+fn note<B: Backend>(terminal: &mut Terminal<B>, text: &str) -> io::Result<()> {
+    let size = terminal.size()?;
+    let text = format!(" {:<40} ", text);
+    let style = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let y = size.height.saturating_sub(2);
+    let cells: Vec<(u16, u16, tui::buffer::Cell)> = text
+        .chars()
+        .take(size.width.saturating_sub(2) as usize)
+        .enumerate()
+        .map(|(i, c)| {
+            let mut cell = tui::buffer::Cell::default();
+            cell.set_char(c).set_style(style);
+            (1 + i as u16, y, cell)
+        })
+        .collect();
+    let backend = terminal.backend_mut();
+    backend.draw(cells.iter().map(|(x, y, cell)| (*x, *y, cell)))?;
+    backend.flush()
+}
+
+// what the terminal tab is called while we're running, like "nyaa: one piece (p3)"
+fn terminal_title(params: &Params) -> String {
+    if params.query.is_empty() {
+        format!("nyaa (p{})", params.page)
+    } else {
+        format!("nyaa: {} (p{})", params.query, params.page)
+    }
+}
+
+// move to the next mode for a key press, None means quit
+async fn handle_key(
+    mode: Mode,
+    code: KeyCode,
+    app: &mut App,
+    params: &mut Params,
+) -> Result<Option<Mode>, Box<dyn Error>> {
+    let next = next_mode(mode, code, app, params).await?;
+    if let Some(Mode::Normal) = next {
+        app.prefetch(params);
+    }
+    // messages are gone with the next key, the log keeps them
+    if let Some(Mode::Modal(Modal::Message(message) | Modal::Error(message))) = &next {
+        app.log(message.clone());
+    }
+    Ok(next)
+}
+
+async fn next_mode(
+    mode: Mode,
+    code: KeyCode,
+    app: &mut App,
+    params: &mut Params,
+) -> Result<Option<Mode>, Box<dyn Error>> {
+    let next = match mode {
+        Mode::Normal => return normal_key(code, None, app, params).await,
+        Mode::PendingCount(mut count) => match code {
+            KeyCode::Char(c @ '0'..='9') => {
+                count.push(c);
+                Mode::PendingCount(count)
+            }
+            KeyCode::Esc => Mode::Normal,
+            _ => return normal_key(code, count.parse().ok(), app, params).await,
+        },
+        Mode::SortPrefix => {
+            if let KeyCode::Char(c @ '1'..='9') = code {
+                app.sort_by_column(c as usize - '1' as usize);
+            }
+            Mode::Normal
+        }
+        Mode::Input(input, mut text, mut cursor) => match code {
+            KeyCode::Enter => submit_input(input, text, app, params).await,
+            KeyCode::Esc => {
+                match input {
+                    Input::Search => app.cancel_live_search(params),
+                    Input::Fuzzy => app.set_fuzzy(""),
+                    _ => {}
+                }
+                Mode::Normal
+            }
+            code => {
+                edit_line(&mut text, &mut cursor, code);
+                // the page narrows as the filter is typed
+                if input == Input::Fuzzy {
+                    app.set_fuzzy(&text);
+                }
+                Mode::Input(input, text, cursor)
+            }
+        },
+        Mode::Modal(Modal::ConfirmQuit) => match code {
+            KeyCode::Char('y' | 'q') => return Ok(None),
+            _ => Mode::Normal,
+        },
+        Mode::Modal(modal) => modal_key(modal, code, app, params).await?,
+    };
+
+    Ok(Some(next))
+}
+
+async fn normal_key(
+    code: KeyCode,
+    count: Option<usize>,
+    app: &mut App,
+    params: &mut Params,
+) -> Result<Option<Mode>, Box<dyn Error>> {
+    let amount = count.unwrap_or(1);
+    let page_amount = u16::try_from(amount).unwrap_or(u16::MAX);
+    match app.keymap.resolve(code) {
+        KeyCode::Char(c @ '0'..='9') => return Ok(Some(Mode::PendingCount(c.to_string()))),
+        // a count was probably meant for something else, q fat-fingered into it
+        KeyCode::Char('q') if count.is_some() => {}
+        KeyCode::Char('q') if app.confirm_quit => {
+            return Ok(Some(Mode::Modal(Modal::ConfirmQuit)));
+        }
+        KeyCode::Char('q') => return Ok(None),
+        KeyCode::Down | KeyCode::Char('j') => app.next_by(amount),
+        KeyCode::Up | KeyCode::Char('k') => app.previous_by(amount),
+        // like vim, 5g is the fifth item and 5G the fifth from the end
+        KeyCode::Char('G') => match count {
+            Some(n) => app.select_index(app.items.len().saturating_sub(n)),
+            None => app.last_item(),
+        },
+        KeyCode::Char('g') => match count {
+            Some(n) => app.select_index(n.saturating_sub(1)),
+            None => app.first_item(),
+        },
+        KeyCode::Char('U') if !app.first_unviewed() => {
+            let message = "Everything here is viewed.".to_string();
+            return Ok(Some(Mode::Modal(Modal::Message(message))));
+        }
+        KeyCode::Char(']') if !app.next_unviewed() => {
+            let message = "Everything here is viewed.".to_string();
+            return Ok(Some(Mode::Modal(Modal::Message(message))));
+        }
+        // at either end there's nowhere to go, so say so rather than refetch
+        KeyCode::Char('n') if params.page >= nyaa::MAX_PAGE => {
+            let message = format!("Page {} is the last page.", nyaa::MAX_PAGE);
+            return Ok(Some(Mode::Modal(Modal::Message(message))));
+        }
+        KeyCode::Char('p') if params.page <= 1 => {
+            let message = "Already on the first page.".to_string();
+            return Ok(Some(Mode::Modal(Modal::Message(message))));
+        }
+        KeyCode::Char('n') => {
+            let previous = params.clone();
+            params.next_page_by(page_amount);
+            return Ok(Some(load_page(app, params, previous).await));
+        }
+        KeyCode::Char('p') => {
+            let previous = params.clone();
+            params.prev_page_by(page_amount);
+            return Ok(Some(load_page(app, params, previous).await));
+        }
+        KeyCode::Char('/') => {
+            if app.live_search {
+                app.before_search = Some((params.clone(), app.page_items.clone()));
+            }
+            return Ok(Some(Mode::Input(Input::Search, String::from(""), 0)));
+        }
+        // an empty list has nothing for these to act on
+        KeyCode::Char(
+            'o' | 'm' | 't' | 'a' | 'D' | 's' | 'T' | 'd' | ' ' | 'y' | 'I' | 'N' | 'f' | 'E',
+        )
+        | KeyCode::Enter
+            if app.selected().is_none() =>
+        {
+            let message = "Nothing selected.".to_string();
+            return Ok(Some(Mode::Modal(Modal::Message(message))));
+        }
+        // a held or repeating key shouldn't spawn a process storm
+        KeyCode::Char('o' | 't' | 'a' | 'D') if !app.may_launch(Instant::now()) => {}
+        KeyCode::Char('o') => {
+            if let Some(item) = app.selected() {
+                let url = format!("https://nyaa.si/view/{}", item.id);
+                if let Err(e) = open_url(&url) {
+                    let message = format!("Couldn't open {}: {}", url, e);
+                    return Ok(Some(Mode::Modal(Modal::Error(message))));
+                }
+                app.log(format!("Opened {}", url));
+            }
+        }
+        KeyCode::Char('m') => {
+            if let Some(item) = app.selected() {
+                return Ok(Some(Mode::Modal(Modal::Magnet(Box::new(item.clone())))));
+            }
+        }
+        KeyCode::Char('t') => {
+            let item = match app.selected() {
+                Some(item) => item,
+                None => return Ok(Some(Mode::Normal)),
+            };
+            if let Some(command) = &app.torrent_command {
+                let command = fill_command(command, item);
+                if let Err(e) = run_command(&command, app.tmux) {
+                    let message = format!("Couldn't run {}: {}", command, e);
+                    return Ok(Some(Mode::Modal(Modal::Error(message))));
+                }
+                app.log(format!("Ran {}", command));
+            } else {
+                if let Err(e) = open_url(&item.torrent) {
+                    let message = format!("Couldn't open {}: {}", item.torrent, e);
+                    return Ok(Some(Mode::Modal(Modal::Error(message))));
+                }
+                app.log(format!("Opened {}", item.torrent));
+            }
+        }
+        KeyCode::Char('a') => {
+            let item = match app.selected() {
+                Some(item) => item,
+                None => return Ok(Some(Mode::Normal)),
+            };
+            let modal = match download_torrent(&app.client, item, &app.download_dir).await {
+                Err(e) => Modal::Error(format!("Couldn't download {}: {}", item.torrent, e)),
+                Ok(path) => match open_url(&path.to_string_lossy()) {
+                    Err(e) => Modal::Error(format!(
+                        "Saved {} but couldn't open it: {}",
+                        path.display(),
+                        e
+                    )),
+                    Ok(()) => Modal::Message(format!("Saved and opened {}", path.display())),
+                },
+            };
+            return Ok(Some(Mode::Modal(modal)));
+        }
+        KeyCode::Char('T') => {
+            let trackers = match app.selected() {
+                Some(item) => magnet_trackers(&item.magnet),
+                None => vec![],
+            };
+            return Ok(Some(Mode::Modal(Modal::Trackers {
+                state: first_selected(trackers.len()),
+                trackers,
+                title: "Trackers (y to copy, q to close)".to_string(),
+            })));
+        }
+        KeyCode::Char('w') => {
+            return Ok(Some(Mode::Input(Input::SaveSearch, String::from(""), 0)));
+        }
+        KeyCode::Char('W') => {
+            let state = first_selected(app.searches.len());
+            return Ok(Some(Mode::Modal(Modal::Searches(state))));
+        }
+        KeyCode::Char('H') => {
+            let state = first_selected(app.saved.history.len());
+            return Ok(Some(Mode::Modal(Modal::History(state))));
+        }
+        KeyCode::Char('R') => return Ok(Some(reload(app, params).await)),
+        KeyCode::Char(':') => return Ok(Some(Mode::Input(Input::Page, String::new(), 0))),
+        KeyCode::Char('b') => {
+            let previous = params.clone();
+            params.set_query("");
+            return Ok(Some(load_page(app, params, previous).await));
+        }
+        KeyCode::Char('h') => return Ok(Some(Mode::Modal(Modal::Help))),
+        KeyCode::Enter | KeyCode::Char('d') => {
+            if app.current.is_none() {
+                app.first_item();
+            }
+            return Ok(Some(Mode::Modal(Modal::Detail)));
+        }
+        KeyCode::Char('c') => {
+            // start on the category that's in use
+            let current = (params.category.as_deref(), params.sub_category.as_deref());
+            let i = category_choices()
+                .iter()
+                .position(|choice| *choice == current);
+            let mut state = ListState::default();
+            state.select(Some(i.unwrap_or(0)));
+            return Ok(Some(Mode::Modal(Modal::CategoryList(state))));
+        }
+        KeyCode::Char('C') => {
+            return Ok(Some(Mode::Modal(Modal::Categories {
+                parent: None,
+                state: first_selected(1),
+            })));
+        }
+        KeyCode::Char(',') => return Ok(Some(Mode::SortPrefix)),
+        KeyCode::Char('r') => app.reverse(),
+        KeyCode::Char('S') => app.cycle_sort(),
+        KeyCode::Char(' ') => app.toggle_mark(),
+        KeyCode::Char('f') => app.toggle_bookmark(),
+        KeyCode::Char('F') => app.toggle_bookmarks_view(),
+        KeyCode::Char('D') => {
+            let item = match app.selected() {
+                Some(item) => item,
+                None => return Ok(Some(Mode::Normal)),
+            };
+            let modal = match download_torrent(&app.client, item, &app.download_dir).await {
+                Ok(path) => Modal::Message(format!("Saved {}", path.display())),
+                Err(e) => Modal::Error(format!("Couldn't download {}: {}", item.torrent, e)),
+            };
+            return Ok(Some(Mode::Modal(modal)));
+        }
+        KeyCode::Char('&') => {
+            let text = app.fuzzy.clone();
+            let cursor = text.len();
+            return Ok(Some(Mode::Input(Input::Fuzzy, text, cursor)));
+        }
+        KeyCode::Esc if !app.fuzzy.is_empty() => app.set_fuzzy(""),
+        KeyCode::Char('X') => return Ok(Some(Mode::Input(Input::Export, String::new(), 0))),
+        KeyCode::Char('A') => {
+            let items = if app.marked.is_empty() {
+                &app.items
+            } else {
+                &app.marked
+            };
+            let dir = &app.download_dir;
+            let path = dir.join(format!("nyaa-aria2-{}.txt", unix_now()));
+            let base = Some(dir.as_path()).filter(|_| app.download_dir_set);
+            let written = std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(&path, aria2_input(items, base)));
+            let modal = match written {
+                Ok(()) => Modal::Message(format!(
+                    "Wrote {} magnets to {}, run aria2c -i on it to download them.",
+                    items.iter().filter(|i| !i.magnet.is_empty()).count(),
+                    path.display()
+                )),
+                Err(e) => Modal::Error(format!("Couldn't write {}: {}", path.display(), e)),
+            };
+            return Ok(Some(Mode::Modal(modal)));
+        }
+        KeyCode::Char(c @ ('y' | 'I' | 'N')) => {
+            let item = match app.selected() {
+                Some(item) => item,
+                None => return Ok(Some(Mode::Normal)),
+            };
+            let (what, text) = match c {
+                'I' => ("id", &item.id),
+                'N' => ("name", &item.name),
+                _ => ("magnet", &item.magnet),
+            };
+            let mode = match copy_to_clipboard(text) {
+                Ok(()) => Modal::Message(format!("Copied the {} for {}", what, item.name)),
+                Err(e) => Modal::Error(format!("Couldn't copy the {}: {}", what, e)),
+            };
+            return Ok(Some(Mode::Modal(mode)));
+        }
+        KeyCode::Char('J') => {
+            let modal = match copy_to_clipboard(&magnets_json(&app.items)) {
+                Ok(()) => Modal::Message(format!("Copied {} items as json.", app.items.len())),
+                Err(e) => Modal::Error(format!("Couldn't copy the items as json: {}", e)),
+            };
+            return Ok(Some(Mode::Modal(modal)));
+        }
+        KeyCode::Char('L') => {
+            // start at the newest entry
+            let mut state = ListState::default();
+            state.select(app.log.len().checked_sub(1));
+            return Ok(Some(Mode::Modal(Modal::Log(state))));
+        }
+        KeyCode::Char('v') => {
+            let state = first_selected(app.marked.len());
+            return Ok(Some(Mode::Modal(Modal::Marked {
+                state,
+                title: MARKED_TITLE.to_string(),
+            })));
+        }
+        KeyCode::Char('~') => app.toggle_column(ColumnKind::Trend),
+        KeyCode::Char('z') => app.toggle_scope(),
+        KeyCode::Char('x') => {
+            let previous = params.clone();
+            params.cycle_filter();
+            params.page = 1;
+            return Ok(Some(load_page(app, params, previous).await));
+        }
+        KeyCode::Char('M') => {
+            let current = app
+                .min_seeders
+                .map(|min| min.to_string())
+                .unwrap_or_default();
+            let cursor = current.len();
+            return Ok(Some(Mode::Input(Input::MinSeeders, current, cursor)));
+        }
+        KeyCode::Char('e') => {
+            app.ephemeral = !app.ephemeral;
+            if app.ephemeral {
+                app.log("Viewed marks are for this session only");
+            } else {
+                // the session-only marks go, back to what ~/.nyaa has
+                app.viewed = app.saved.viewed.clone();
+                app.log("Viewed marks are saved again");
+            }
+        }
+        KeyCode::Char('#') => app.toggle_column(ColumnKind::Hash),
+        KeyCode::Char('E') => {
+            if let Some(item) = app.selected() {
+                return Ok(Some(Mode::Modal(Modal::Name(item.name.clone()))));
+            }
+        }
+        KeyCode::Char('%') => app.toggle_column(ColumnKind::Category),
+        KeyCode::Char('@') => app.relative_dates = !app.relative_dates,
+        KeyCode::Char('u') => {
+            let url = app.client.url(params)?.to_string();
+            return Ok(Some(Mode::Modal(Modal::Url(url))));
+        }
+        KeyCode::Char('s') => {
+            let ids = app.viewed_targets();
+            let viewed = app.viewed_flip(&ids);
+            let count = app.marking_affects(&ids, viewed);
+            if count > app.confirm_mark {
+                let modal = Modal::ConfirmMark { ids, viewed, count };
+                return Ok(Some(Mode::Modal(modal)));
+            }
+            mark_viewed(app, &ids, viewed);
+        }
+        _ => {}
+    }
+
+    Ok(Some(Mode::Normal))
+}
+
+fn mark_viewed(app: &mut App, ids: &[u64], viewed: bool) {
+    app.set_viewed(ids, viewed);
+    let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+    let state = if viewed { "viewed" } else { "unviewed" };
+    app.log(format!("Marked {} as {}", ids.join(", "), state));
+}
+
+async fn submit_input(input: Input, text: String, app: &mut App, params: &mut Params) -> Mode {
+    match input {
+        Input::Search => {
+            app.before_search = None;
+            let previous = params.clone();
+            params.set_query(&text);
+            // only searches that went through make it into the history
+            let mode = load_page(app, params, previous).await;
+            if matches!(mode, Mode::Normal) {
+                app.remember_search(&text);
+            }
+            return mode;
+        }
+        // pages past the last, even past what a u16 holds, go to the last
+        Input::Page => match text.trim().parse::<u64>() {
+            Ok(page) => {
+                let previous = params.clone();
+                params.page = page.clamp(1, u64::from(nyaa::MAX_PAGE)) as u16;
+                return load_page(app, params, previous).await;
+            }
+            Err(_) if text.trim().is_empty() => {}
+            Err(_) => {
+                let message = format!("Not a page number: {}", text.trim());
+                return Mode::Modal(Modal::Message(message));
+            }
+        },
+        Input::MinSeeders => {
+            if let Ok(min) = text.trim().parse::<u32>() {
+                app.set_min_seeders(Some(min));
+            }
+        }
+        // the filter is already applied, enter only keeps it
+        Input::Fuzzy => {}
+        Input::Export => {
+            let dir = app
+                .export_dir
+                .clone()
+                .unwrap_or_else(|| app.download_dir.clone());
+            let modal = match export_items(&app.items, &dir, &text, unix_now()) {
+                Ok(path) => Modal::Message(format!(
+                    "Exported {} items to {}",
+                    app.items.len(),
+                    path.display()
+                )),
+                Err(e) => Modal::Error(format!("Couldn't export the items: {}", e)),
+            };
+            return Mode::Modal(modal);
+        }
+        Input::SaveSearch => {
+            let name = text.trim();
+            if !name.is_empty() {
+                app.save_search(name, params);
+                let searches = app.searches.clone();
+                app.persist(|| save_searches(&searches));
+            }
+        }
+    }
+
+    Mode::Normal
+}
+
+// hand a magnet to NYAA_MAGNET_COMMAND or the system opener, the error popup if that failed
+fn open_magnet(app: &mut App, item: &Response) -> Option<Modal> {
+    match app.magnet_command_for(item) {
+        Some(command) => {
+            let command = fill_command(command, item);
+            if let Err(e) = run_command(&command, app.tmux) {
+                let message = format!("Couldn't run {}: {}", command, e);
+                return Some(Modal::Error(message));
+            }
+            app.log(format!("Ran {}", command));
+        }
+        None => {
+            if let Err(e) = open_url(&item.magnet) {
+                let message = format!("Couldn't open a magnet: {}", e);
+                return Some(Modal::Error(message));
+            }
+            app.log("Opened a magnet");
+        }
+    }
+    None
+}
+
+async fn modal_key(
+    modal: Modal,
+    code: KeyCode,
+    app: &mut App,
+    params: &mut Params,
+) -> Result<Mode, Box<dyn Error>> {
+    let modal = match modal {
+        // ConfirmQuit is answered in next_mode, which can quit
+        Modal::Help
+        | Modal::Url(_)
+        | Modal::Message(_)
+        | Modal::Error(_)
+        | Modal::Name(_)
+        | Modal::ConfirmQuit => return Ok(Mode::Normal),
+        Modal::Magnet(item) => match code {
+            KeyCode::Enter | KeyCode::Char('o' | 'm') => {
+                if !app.may_launch(Instant::now()) {
+                    return Ok(Mode::Modal(Modal::Magnet(item)));
+                }
+                return Ok(match open_magnet(app, &item) {
+                    Some(error) => Mode::Modal(error),
+                    None => Mode::Normal,
+                });
+            }
+            KeyCode::Char('y') => {
+                return Ok(Mode::Modal(match copy_to_clipboard(&item.magnet) {
+                    Ok(()) => Modal::Message(format!("Copied the magnet for {}", item.name)),
+                    Err(e) => Modal::Error(format!("Couldn't copy the magnet: {}", e)),
+                }))
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(Mode::Normal),
+            _ => Modal::Magnet(item),
+        },
+        Modal::Detail => {
+            match code {
+                KeyCode::Down | KeyCode::Char('j') => app.next_by(1),
+                KeyCode::Up | KeyCode::Char('k') => app.previous_by(1),
+                _ => return Ok(Mode::Normal),
+            }
+            Modal::Detail
+        }
+        Modal::ConfirmMark { ids, viewed, .. } => {
+            if let KeyCode::Char('y') = code {
+                mark_viewed(app, &ids, viewed);
+            }
+            return Ok(Mode::Normal);
+        }
+        Modal::Trackers {
+            trackers,
+            mut state,
+            mut title,
+        } => {
+            match code {
+                KeyCode::Char('y') => {
+                    if let Some(tracker) = trackers.get(state.selected().unwrap_or(0)) {
+                        title = match copy_to_clipboard(tracker) {
+                            Ok(()) => "Trackers (copied)".to_string(),
+                            Err(e) => format!("Trackers ({})", e),
+                        };
+                        app.log(format!("Copied tracker {}: {}", tracker, title));
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(Mode::Normal),
+                code => step_list(&mut state, trackers.len(), code),
+            }
+            Modal::Trackers {
+                trackers,
+                state,
+                title,
+            }
+        }
+        Modal::Searches(mut state) => {
+            let i = state.selected().unwrap_or(0);
+            match code {
+                KeyCode::Enter => {
+                    if let Some(search) = app.searches.get(i).cloned() {
+                        let previous = params.clone();
+                        search.apply(app, params);
+                        return Ok(load_page(app, params, previous).await);
+                    }
+                    return Ok(Mode::Normal);
+                }
+                KeyCode::Char('d') if i < app.searches.len() => {
+                    app.searches.remove(i);
+                    let searches = app.searches.clone();
+                    app.persist(|| save_searches(&searches));
+                    if app.searches.is_empty() {
+                        state.select(None);
+                    } else {
+                        state.select(Some(i.min(app.searches.len() - 1)));
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(Mode::Normal),
+                code => step_list(&mut state, app.searches.len(), code),
+            }
+            Modal::Searches(state)
+        }
+        Modal::History(mut state) => {
+            let i = state.selected().unwrap_or(0);
+            match code {
+                KeyCode::Enter => {
+                    if let Some(query) = app.saved.history.get(i).cloned() {
+                        let previous = params.clone();
+                        params.set_query(&query);
+                        params.page = 1;
+                        let mode = load_page(app, params, previous).await;
+                        if matches!(mode, Mode::Normal) {
+                            app.remember_search(&query);
+                        }
+                        return Ok(mode);
+                    }
+                    return Ok(Mode::Normal);
+                }
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(Mode::Normal),
+                code => step_list(&mut state, app.saved.history.len(), code),
+            }
+            Modal::History(state)
+        }
+        Modal::Marked {
+            mut state,
+            mut title,
+        } => {
+            match code {
+                KeyCode::Char('y') if !app.marked.is_empty() => {
+                    let names: Vec<&str> = app.marked.iter().map(|i| i.name.as_str()).collect();
+                    title = match copy_to_clipboard(&names.join("\n")) {
+                        Ok(()) => format!("Marked (copied {} names)", names.len()),
+                        Err(e) => format!("Marked ({})", e),
+                    };
+                    app.log(format!("Copied marked names: {}", title));
+                }
+                KeyCode::Char('m') if !app.marked.is_empty() => {
+                    let magnets = marked_magnets(&app.marked);
+                    title = match copy_to_clipboard(&magnets.join("\n")) {
+                        Ok(()) => format!("Marked (copied {} magnets)", magnets.len()),
+                        Err(e) => format!("Marked ({})", e),
+                    };
+                    app.log(format!("Copied marked magnets: {}", title));
+                }
+                KeyCode::Char('c') => {
+                    app.marked.clear();
+                    state.select(None);
+                }
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(Mode::Normal),
+                code => step_list(&mut state, app.marked.len(), code),
+            }
+            Modal::Marked { state, title }
+        }
+        Modal::CategoryList(mut state) => {
+            let choices = category_choices();
+            match code {
+                KeyCode::Enter => {
+                    if let Some((category, sub)) = state.selected().and_then(|i| choices.get(i)) {
+                        let previous = params.clone();
+                        params.set_category(*category, *sub);
+                        params.page = 1;
+                        return Ok(load_page(app, params, previous).await);
+                    }
+                    return Ok(Mode::Normal);
+                }
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(Mode::Normal),
+                code => step_list(&mut state, choices.len(), code),
+            }
+            Modal::CategoryList(state)
+        }
+        Modal::Log(mut state) => {
+            match code {
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(Mode::Normal),
+                code => step_list(&mut state, app.log.len(), code),
+            }
+            Modal::Log(state)
+        }
+        Modal::Categories { parent, mut state } => {
+            let i = state.selected().unwrap_or(0);
+            match (code, parent) {
+                // the first line of each level picks the whole level
+                (KeyCode::Enter, None) if i > 0 => {
+                    return Ok(Mode::Modal(Modal::Categories {
+                        parent: Some(i - 1),
+                        state: first_selected(1),
+                    }));
+                }
+                (KeyCode::Enter, _) => {
+                    let category = parent.map(|p| &CATEGORIES[p]);
+                    let sub = category.and_then(|c| i.checked_sub(1).map(|s| c.subs[s].0));
+                    let previous = params.clone();
+                    params.set_category(category.map(|c| c.id), sub);
+                    params.page = 1;
+                    return Ok(load_page(app, params, previous).await);
+                }
+                (KeyCode::Backspace | KeyCode::Char('h'), Some(p)) => {
+                    let mut state = ListState::default();
+                    state.select(Some(p + 1));
+                    return Ok(Mode::Modal(Modal::Categories {
+                        parent: None,
+                        state,
+                    }));
+                }
+                (KeyCode::Esc | KeyCode::Char('q'), _) => return Ok(Mode::Normal),
+                (code, _) => step_list(&mut state, category_lines(parent).len(), code),
+            }
+            Modal::Categories { parent, state }
+        }
+    };
+
+    Ok(Mode::Modal(modal))
+}
+
+// the lines of one level of the category tree
+fn category_lines(parent: Option<usize>) -> Vec<String> {
+    match parent {
+        None => std::iter::once("All categories")
+            .chain(CATEGORIES.iter().map(|c| c.name))
+            .map(str::to_string)
+            .collect(),
+        Some(p) => std::iter::once(format!("All {}", CATEGORIES[p].name))
+            .chain(CATEGORIES[p].subs.iter().map(|(_, name)| name.to_string()))
+            .collect(),
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App, mode: &mut Mode, params: &Params) {
+    match mode {
+        Mode::Normal => ui(f, app, params, ""),
+        Mode::PendingCount(count) => ui(f, app, params, count),
+        Mode::SortPrefix => ui(f, app, params, ","),
+        Mode::Input(input, text, cursor) => search_ui(f, &app.theme, input.label(), text, *cursor),
+        Mode::Modal(Modal::Help) => popup_ui(f, &app.theme),
+        Mode::Modal(Modal::Url(url)) => message_ui(f, "API URL", url),
+        Mode::Modal(Modal::Message(message)) => message_ui(f, "", message),
+        Mode::Modal(Modal::Error(message)) => {
+            ui(f, app, params, "");
+            error_popup(f, message)
+        }
+        Mode::Modal(Modal::ConfirmQuit) => {
+            ui(f, app, params, "");
+            let area = centered(f.size(), 20, 3);
+            overlay(f, area, Block::default().borders(Borders::ALL), "Quit? y/n");
+        }
+        Mode::Modal(Modal::ConfirmMark { viewed, count, .. }) => {
+            let text = format!(
+                "This will mark {} items as {}, continue? (y/n)",
+                count,
+                if *viewed { "viewed" } else { "unviewed" }
+            );
+            message_ui(f, "Mark as viewed", &text)
+        }
+        Mode::Modal(Modal::Trackers {
+            trackers,
+            state,
+            title,
+        }) => {
+            let empty = "This magnet has no trackers.";
+            list_ui(f, &app.theme, title, trackers, empty, state)
+        }
+        Mode::Modal(Modal::Searches(state)) => {
+            let lines: Vec<String> = app.searches.iter().map(|s| s.describe()).collect();
+            let title = "Saved searches (enter to run, d to delete, q to close)";
+            let empty = "No saved searches yet, press w to save one.";
+            list_ui(f, &app.theme, title, &lines, empty, state)
+        }
+        Mode::Modal(Modal::History(state)) => {
+            let title = "Recent searches (enter to run, q to close)";
+            let empty = "No searches yet, press / to search.";
+            list_ui(f, &app.theme, title, &app.saved.history, empty, state)
+        }
+        Mode::Modal(Modal::Detail) => {
+            let text = match app.current.and_then(|i| app.items.get(i)) {
+                Some(item) => detail_text(item, app.exact_sizes),
+                None => String::from("Nothing selected."),
+            };
+            ui(f, app, params, "");
+            detail_popup(f, &text)
+        }
+        Mode::Modal(Modal::Name(name)) => {
+            ui(f, app, params, "");
+            let size = f.size();
+            let area = centered(size, size.width * 4 / 5, 5);
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Name (any key to close)");
+            overlay(f, area, block, name);
+        }
+        Mode::Modal(Modal::Magnet(item)) => {
+            ui(f, app, params, "");
+            let size = f.size();
+            let area = centered(size, size.width * 4 / 5, 10);
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("Magnet (o to open, y to copy, q to close)");
+            overlay(f, area, block, &item.magnet);
+        }
+        Mode::Modal(Modal::Marked { state, title }) => {
+            let names: Vec<String> = app.marked.iter().map(|i| i.name.clone()).collect();
+            let empty = "Nothing is marked, press space on a row to mark it.";
+            list_ui(f, &app.theme, title, &names, empty, state)
+        }
+        Mode::Modal(Modal::CategoryList(state)) => {
+            let lines: Vec<String> = category_choices()
+                .into_iter()
+                .map(|(c, s)| category_label(c, s).unwrap_or_else(|| "All categories".to_string()))
+                .collect();
+            list_ui(
+                f,
+                &app.theme,
+                "Category (enter to pick, q to close)",
+                &lines,
+                "",
+                state,
+            )
+        }
+        Mode::Modal(Modal::Log(state)) => {
+            let lines: Vec<String> = app
+                .log
+                .iter()
+                .map(|(at, entry)| format!("{} {}", clock(*at), entry))
+                .collect();
+            list_ui(
+                f,
+                &app.theme,
+                LOG_TITLE,
+                &lines,
+                "Nothing has happened yet.",
+                state,
+            )
+        }
+        Mode::Modal(Modal::Categories { parent, state }) => {
+            let title = match parent {
+                None => "Categories (enter to open, q to close)",
+                Some(_) => "Categories (enter to pick, backspace to go up)",
+            };
+            list_ui(f, &app.theme, title, &category_lines(*parent), "", state)
+        }
+    }
+}
+
+// j/k movement shared by the list popups
+fn step_list(state: &mut ListState, len: usize, code: KeyCode) {
+    let i = state.selected().unwrap_or(0);
+    match code {
+        KeyCode::Down | KeyCode::Char('j') if i + 1 < len => state.select(Some(i + 1)),
+        KeyCode::Up | KeyCode::Char('k') if len > 0 => state.select(Some(i.saturating_sub(1))),
+        _ => {}
+    }
+}
+
+// the cursor is the character under it drawn reversed, or a reversed space at the end
+fn search_ui<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &theme::Theme,
+    label: &str,
+    text: &str,
+    cursor: usize,
+) {
+    let size = f.size();
+
+    let chunks = Layout::default()
+        .constraints([Constraint::Percentage(20)].as_ref())
+        .split(size);
+
+    let (before, rest) = text.split_at(cursor.min(text.len()));
+    let under = rest.chars().next().map_or(0, char::len_utf8);
+    let (under, after) = rest.split_at(under);
+    let line = Spans::from(vec![
+        Span::raw(label),
+        Span::raw(before),
+        Span::styled(
+            if under.is_empty() { " " } else { under },
+            Style::default().add_modifier(Modifier::REVERSED),
+        ),
+        Span::raw(after),
+    ]);
+    let paragraph = Paragraph::new(line)
+        .style(theme.text())
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, chunks[0]);
+}
+
+// the footer under the table, like "page 3 | 75 results | one piece"
+fn status_line(app: &App, params: &Params) -> String {
+    let plural = if app.items.len() == 1 { "" } else { "s" };
+    let mut results = format!("{} result{}", app.items.len(), plural);
+    if app.scope == Scope::Page && app.items.len() != app.page_items.len() {
+        results.push_str(&format!(" (of {})", app.page_items.len()));
+    }
+    let query = if params.query.is_empty() {
+        "no query".to_string()
+    } else {
+        format!("\"{}\"", params.query)
+    };
+    let mut line = format!("page {} | {} | {}", params.page, results, query);
+    if let Some(fetched) = app.offline {
+        let age = ago(unix_now().saturating_sub(fetched));
+        line = format!("offline, cached {} | {}", age, line);
+    }
+    if let Some(label) = nyaa::filter_label(params.filter) {
+        line.push_str(&format!(" | {}", label));
+    }
+    if !app.fuzzy.is_empty() {
+        line.push_str(&format!(" | matching \"{}\"", app.fuzzy));
+    }
+    line
+}
+
+// pending is whatever partial command has been typed, shown in the title
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App, params: &Params, pending: &str) {
+    let rects = Layout::default()
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .margin(1)
+        .split(f.size());
+
+    let theme = app.theme;
+    // inside the borders and under the header's two lines
+    let table = rects[0];
+    app.table_rows = Rect {
+        x: table.x + 1,
+        y: table.y + 3,
+        width: table.width.saturating_sub(2),
+        height: table.height.saturating_sub(4),
+    };
+    app.table_window = row_window(
+        app.table_window.0,
+        app.state.selected(),
+        app.items.len(),
+        app.table_rows.height,
+    );
+    let columns = app.columns();
+    let header_cells = columns
+        .iter()
+        .map(|c| Cell::from(c.title).style(theme.header()));
+    let header = Row::new(header_cells)
+        .style(theme.header_row())
+        .height(1)
+        .bottom_margin(1);
+    let now = unix_now();
+    // roughly how many cells a column gets, after the borders and ">> "
+    let cells = |percent: u16| rects[0].width.saturating_sub(5) as usize * percent as usize / 100;
+    let size_width = columns
+        .iter()
+        .find(|c| c.kind == ColumnKind::Size)
+        .map_or(0, |c| cells(c.width));
+    let rest = 100 - columns.iter().map(|c| c.width).sum::<u16>();
+    // less one for the space between columns
+    let name_width = cells(rest).saturating_sub(1);
+    let rows = app.items.iter().map(|item| {
+        let Response {
+            id,
+            date,
+            name,
+            filesize,
+            seeders,
+            leechers,
+            ..
+        } = item;
+        let is_viewed = app.is_viewed(item);
+        let viewed = match (is_viewed, app.ascii) {
+            (true, false) => "✅",
+            (false, false) => "❌",
+            (true, true) => "v",
+            (false, true) => "-",
+        };
+        let mut viewed = vec![Span::styled(viewed, theme.viewed_marker(is_viewed))];
+        if let Some(color) = freshness(date, now) {
+            let dot = if app.ascii { "*" } else { "●" };
+            viewed.push(Span::styled(dot, Style::default().fg(color)));
+        }
+        let cells = columns.iter().map(|c| match c.kind {
+            ColumnKind::Viewed => Cell::from(Spans::from(viewed.clone())),
+            ColumnKind::Name => {
+                let name = ellipsize(name, name_width);
+                Cell::from(Spans::from(highlight_matches(
+                    &name,
+                    &params.query,
+                    &app.fuzzy,
+                )))
+            }
+            ColumnKind::Date if app.relative_dates => Cell::from(relative_date(date, now)),
+            ColumnKind::Date => Cell::from(date.to_string()),
+            ColumnKind::Size => Cell::from(fit_size(filesize, size_width)),
+            ColumnKind::Seeders => {
+                let cell = Cell::from(seeders.to_string());
+                match seeder_color(seeders, app.seeder_colors) {
+                    Some(color) => cell.style(Style::default().fg(color)),
+                    None => cell,
+                }
+            }
+            ColumnKind::Leechers => Cell::from(leechers.to_string()),
+            ColumnKind::Trend => {
+                let history = app.trend.get(id).map(Vec::as_slice).unwrap_or(&[]);
+                Cell::from(sparkline(history, app.ascii))
+            }
+            ColumnKind::Bookmark => match (app.is_bookmarked(id), app.ascii) {
+                (true, false) => Cell::from("★"),
+                (true, true) => Cell::from("*"),
+                (false, _) => Cell::from(""),
+            },
+            ColumnKind::Status => match status_color(&item.status) {
+                Some(color) => {
+                    let badge = match (app.ascii, color) {
+                        (false, _) => "●",
+                        (true, Color::Green) => "T",
+                        (true, _) => "R",
+                    };
+                    Cell::from(badge).style(Style::default().fg(color))
+                }
+                None => Cell::from(""),
+            },
+            ColumnKind::Hash => Cell::from(short_hash(&item.hash)),
+            ColumnKind::Category => Cell::from(short_category(&item.category, &item.sub_category)),
+        });
+        let row = Row::new(cells).height(ROW_HEIGHT).bottom_margin(1);
+        if app.is_marked(id) {
+            row.style(theme.marked())
+        } else {
+            row
+        }
+    });
+    let mut title = match app.sort {
+        Some((key, ascending)) => {
+            format!(
+                "Table (by {} {})",
+                key.name(),
+                if ascending { "↑" } else { "↓" }
+            )
+        }
+        None => "Table".to_string(),
+    };
+    let category = category_label(params.category.as_deref(), params.sub_category.as_deref());
+    if let Some(category) = category {
+        title.push_str(&format!(" in {}", category));
+    }
+    match (app.show_bookmarks, app.scope) {
+        (true, _) => title.push_str(", bookmarks"),
+        (false, Scope::Page) => title.push_str(&format!(", page {}", params.page)),
+        (false, Scope::All) => {
+            title.push_str(&format!(", all {} fetched pages", app.pages.len().max(1)))
+        }
+    }
+    if !pending.is_empty() {
+        title.push_str(&format!(" [{}]", pending));
+    }
+    if nyaa::rate_limited() {
+        title.push_str(" waiting (rate limit)");
+    }
+    if let Some(min) = app.min_seeders {
+        let at_least = if app.ascii { ">=" } else { "≥" };
+        title.push_str(&format!(", seeders {} {}", at_least, min));
+    }
+    if app.ephemeral {
+        title.push_str(" (marks not saved)");
+    }
+    if !app.saving {
+        title.push_str(" persistence disabled — home not writable");
+    }
+    let widths: Vec<Constraint> = columns
+        .iter()
+        .map(|c| match c.kind {
+            ColumnKind::Name => Constraint::Percentage(rest),
+            _ => Constraint::Percentage(c.width),
+        })
+        .collect();
+    let t = Table::new(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(theme.border())
+                .title(title),
+        )
+        .highlight_style(theme.selected())
+        .highlight_symbol(">> ")
+        .widths(&widths);
+    f.render_stateful_widget(t, rects[0], &mut app.state);
+
+    // an empty table looks broken, so say why it's empty
+    if app.items.is_empty() {
+        let text = if app.show_bookmarks {
+            "No bookmarks yet"
+        } else {
+            "No results found"
+        };
+        let area = centered(rects[0], text.len() as u16, 1);
+        f.render_widget(Paragraph::new(text).alignment(Alignment::Center), area);
+    }
+
+    // a scrollbar over the right border. rows take 4 lines each, the header 2
+    if app.scrollbar && rects[0].height > 2 && rects[0].width > 0 {
+        let area = Rect {
+            x: rects[0].x + rects[0].width - 1,
+            y: rects[0].y + 1,
+            width: 1,
+            height: rects[0].height - 2,
+        };
+        let visible =
+            (usize::from(area.height.saturating_sub(2)) / usize::from(ROW_HEIGHT + 1)).max(1);
+        let selected = app.current.unwrap_or(0);
+        let (thumb, track) = if app.ascii {
+            ("#", "|")
+        } else {
+            ("█", "│")
+        };
+        let cells = scrollbar(app.items.len(), visible, selected, area.height as usize);
+        if cells.contains(&true) {
+            let lines: Vec<Spans> = cells
+                .iter()
+                .map(|on| Spans::from(if *on { thumb } else { track }))
+                .collect();
+            f.render_widget(Paragraph::new(lines), area);
+        }
+    }
+
+    f.render_widget(Paragraph::new(status_line(app, params)), rects[1]);
+}
+
+fn popup_ui<B: Backend>(f: &mut Frame<B>, theme: &theme::Theme) {
+    let size = f.size();
+
+    const HELP_TEXT: &str = "
+/ to search, left/right, home/end and delete edit anywhere in what's typed.
+R to refetch the current page, like for fresh seeder counts.
+s to mark the selected item (or every marked item) as viewed, again to mark it unviewed.
+e to switch s between saving marks and only keeping them for this session (dropped when switched back).
+<number> n to go to the next page (like 5n to go 5 more pages)
+<number> p to go to the prev page (like 5p to go 5 fewer pages)
+: to type a page number to go straight to (1 to 1000).
+<number> j or down arrow to go down one item.
+<number> k or up arrow to up one item.
+g or G to go to the first or last item, <number> g to go to that item and <number> G to that item from the end.
+U to jump to the first item that isn't viewed yet, ] to the next one after the selection.
+o to open the selected item in the web browser.
+E to show the selected item's whole name, when the column cuts it off with a ….
+enter or d to show every detail of the selected item, j/k move between items while it's open and any other key closes it.
+m to show the selected item's magnet link, then o to open it (or run NYAA_MAGNET_COMMAND) or y to copy it.
+t to open up the selected item's torrent link.
+y to copy the selected item's magnet link, I its id and N its name.
+a to download the selected item's .torrent and open it in your torrent app, D to only download it.
+T to list the selected item's trackers (y copies one).
+u to show the api url for the current page.
+w to save the current search under a name, W to pick a saved search.
+H to pick one of the last 20 searches typed with /.
+c to pick a category (or sub category) to filter by from a list, C to browse them as a tree.
+~ to toggle a column charting each item's seeders over the session.
+% to toggle a column with each item's category, shortened (like Anime/Eng).
+@ to switch the date column between the upload date and how long ago that was.
+# to toggle a column with the start of each item's info hash.
+x to switch between every upload, no remakes and trusted uploads only, which refetches.
+M to only show items with at least some number of seeders, 0 shows them all again.
+S to sort by the next field (name, date, size, seeders, leechers), r to reverse the order of the results.
+z to switch between showing this page and every page fetched for the search, which sorting and s then act on.
+space to mark the selected item, v to review (and copy the names or magnets of) marked items.
+f to bookmark the selected item (kept across restarts), F to switch between the page and the bookmarks.
+L to show a log of what happened this session, like searches, opened links and errors.
+J to copy the items on screen, in order, as a json array of name, magnet, hash and size_bytes.
+A to write the marked (or all) magnets to an aria2 input file in the download directory.
+Click a row to select it and double click it for its details, the mouse wheel moves the selection.
+& to narrow the page to names with the typed letters in order, without refetching. esc clears it.
+X to save the items on screen, every field, to a .json file named at the prompt.
+,<number> to sort by that column (Name 2, Date 3, Size 4, Seeders 5, Leechers 6), again to reverse.
+
+A dot next to the viewed mark is green for uploads from the last day, yellow for the last week.
+A dot after the Fav column is green for trusted uploads and red for remakes (T and R in ascii).
+Set NYAA_REFRESH=<seconds> to refetch the page on a timer, which fills in the trend column.
+Set NYAA_OPENER (like \"firefox --new-tab\") to open pages and files with it instead of the system default.
+Set NYAA_MAGNET_COMMAND (or magnet_command in ~/.nyaa.toml, like \"aria2c {magnet}\") to run a command for m instead.
+Set NYAA_TORRENT_COMMAND (or torrent_command, like \"transmission-remote -a {torrent}\") to run one for t.
+Both fill in {magnet}, {torrent} and {url} (the nyaa.si page) for the selected item.
+Set NYAA_CATEGORY_COMMANDS (like \"Anime=aria2c {magnet}; Software/Games=qbittorrent {magnet}\"), or the same
+pairs under [category_commands] in ~/.nyaa.toml (like \"Software/Games\" = \"qbittorrent {magnet}\"),
+to run a different command for m depending on the item's category or category/sub category.
+Set NYAA_TMUX=1 to run that command in a new tmux window when inside tmux.
+Set NYAA_CONFIRM_MARK=<rows> (or confirm_mark in ~/.nyaa.toml) to change how many items s can flip before it asks first.
+Set NYAA_DEFAULT_SORT (or default_sort in ~/.nyaa.toml) to <key>[:asc|:desc], like seeders:desc, to sort
+every page as it loads. The variable wins over the file, and --sort, ,<number>, r or a saved search wins
+over both for the rest of the session.
+Set NYAA_CONFIRM_QUIT=1 (or confirm_quit = true in ~/.nyaa.toml) to have q ask before quitting.
+Set NYAA_LIVE_SEARCH=1 to search as you type, enter keeps the results and esc goes back.
+Set NYAA_EXACT_SIZES=1 to show sizes down to the byte in the detail view.
+Set NYAA_SCROLLBAR=0 (or scrollbar = false in ~/.nyaa.toml) to hide the scrollbar drawn on the table's right edge for long lists.
+Set NYAA_MAX_FPS (or max_fps in ~/.nyaa.toml) to change how often the screen redraws while keys are held (0 for always).
+When the api can't be reached at startup, the last page that loaded comes up from ~/.nyaa_cache.json.
+Colors go under [colors] in ~/.nyaa.toml: header, header_bg, selected, selected_bg, marked, viewed, unviewed, text
+and border, each a name like lightblue, a number from 0 to 255 or #rrggbb.
+Keys outside of popups can be moved under [keys] in ~/.nyaa.toml, like next_page = \"N\" or mark = \"space\".
+Set NYAA_SEEDER_COLORS (or seeder_colors in ~/.nyaa.toml) to <red below>,<green from>, like 5,50 (1,10 by default).
+Set NYAA_TIMEOUT (or timeout in ~/.nyaa.toml) to the seconds a request may take before it fails (10 by default).
+Set NYAA_RETRIES (or retries in ~/.nyaa.toml) to how often a fetch that timed out or got a 5xx is tried again (2 by default).
+Set NYAA_COMPRESSION=0 (or compression = false in ~/.nyaa.toml) to stop asking the api for gzip or deflate answers.
+Set NYAA_RATE_LIMIT (or rate_limit in ~/.nyaa.toml) to the requests per second allowed to the api (2 by default, 0 for no limit).
+Set NYAA_LAUNCH_COOLDOWN_MS (or launch_cooldown_ms in ~/.nyaa.toml) to how long o/m/t/a/D ignore repeats after launching something (300).
+Set NYAA_API_URL (or api_url in ~/.nyaa.toml) to use a self-hosted nyaa-api instead of nyaa-api.fly.dev.
+Set NYAA_DOWNLOAD_DIR (or download_dir in ~/.nyaa.toml) to save .torrent files somewhere other than ~/Downloads.
+Set NYAA_EXPORT_DIR (or export_dir) to have X write its json somewhere other than the download directory.
+Set NYAA_ASCII=1 to use plain ascii for these markers. They fall back to ascii on their own
+when the terminal doesn't draw emoji two cells wide, NYAA_EMOJI_WIDTH=2 overrides that check.
+Set NYAA_RETRY_EMPTY=0 to stop retrying empty results while the api warms up.
+";
+    let paragraph = Paragraph::new(Span::from(HELP_TEXT))
+        .style(theme.text())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(theme.border()),
+        )
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, size);
+}
+
+fn list_ui<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &theme::Theme,
+    title: &str,
+    lines: &[String],
+    empty: &str,
+    state: &mut ListState,
+) {
+    let size = f.size();
+
+    let block = Block::default().borders(Borders::ALL).title(title);
+    if lines.is_empty() {
+        let paragraph = Paragraph::new(Span::from(empty)).block(block);
+        f.render_widget(paragraph, size);
+        return;
+    }
+
+    let items: Vec<ListItem> = lines.iter().map(|l| ListItem::new(l.as_str())).collect();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme.selected())
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, size, state);
+}
+
+// the char positions in name of pattern's letters, in order and ignoring case
+// and spaces, or None if they aren't all there
+fn fuzzy_match(name: &str, pattern: &str) -> Option<Vec<usize>> {
+    let same = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+    let mut chars = name.chars().enumerate();
+    pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|p| chars.find(|(_, c)| same(*c, p)).map(|(i, _)| i))
+        .collect()
+}
+
+// split name into spans, emphasizing every case-insensitive match of a
+// query word and the letters of the fuzzy filter. overlapping and touching
+// matches merge into one span
+fn highlight_matches(name: &str, query: &str, fuzzy: &str) -> Vec<Span<'static>> {
+    let chars: Vec<char> = name.chars().collect();
+    let same = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+    let mut matched = vec![false; chars.len()];
+    for word in query.split_whitespace() {
+        let word: Vec<char> = word.chars().collect();
+        if word.len() > chars.len() {
+            continue;
+        }
+        for start in 0..=chars.len() - word.len() {
+            if word.iter().zip(&chars[start..]).all(|(a, b)| same(*a, *b)) {
+                matched[start..start + word.len()].fill(true);
+            }
+        }
+    }
+    for i in fuzzy_match(name, fuzzy).unwrap_or_default() {
+        matched[i] = true;
+    }
+
+    let emphasis = Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let mut spans = vec![];
+    let mut start = 0;
+    for end in 1..=chars.len() {
+        if end == chars.len() || matched[end] != matched[start] {
+            let text: String = chars[start..end].iter().collect();
+            spans.push(if matched[start] {
+                Span::styled(text, emphasis)
+            } else {
+                Span::raw(text)
+            });
+            start = end;
+        }
+    }
+    spans
+}
+
+fn detail_text(item: &Response, exact_sizes: bool) -> String {
+    let size = match parse_filesize(&item.filesize) {
+        Some(bytes) if exact_sizes => {
+            format!("{} bytes ({})", group_digits(bytes), human_size(bytes))
+        }
+        _ => display_size(&item.filesize),
+    };
+    let category = [item.category.as_str(), item.sub_category.as_str()]
+        .into_iter()
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ");
+    format!(
+        "{}\n\nid: {}\ndate: {}\nsize: {}\ncategory: {}\nstatus: {}\n\
+         seeders: {}\nleechers: {}\ncompleted: {}\n\nhash: {}\ntorrent: {}\nmagnet: {}",
+        item.name,
+        item.id,
+        item.date,
+        size,
+        category,
+        item.status,
+        item.seeders,
+        item.leechers,
+        item.completed,
+        item.hash,
+        item.torrent,
+        item.magnet,
+    )
+}
+
+// a box in the middle of the screen, over whatever was drawn before
+fn error_popup<B: Backend>(f: &mut Frame<B>, msg: &str) {
+    let size = f.size();
+    let area = centered(size, (size.width * 3 / 5).max(20), 7);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red))
+        .title("Error (any key to close)");
+    overlay(f, area, block, msg);
+}
+
+// every field of the selected item, over the table
+fn detail_popup<B: Backend>(f: &mut Frame<B>, text: &str) {
+    let size = f.size();
+    let area = centered(size, size.width * 4 / 5, size.height * 4 / 5);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Details (j/k for the next/previous item, any other key to close)");
+    overlay(f, area, block, text);
+}
+
+// a width by height box in the middle of area, no bigger than area
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+// wrapped text in a block, blanking whatever was drawn under it
+fn overlay<B: Backend>(f: &mut Frame<B>, area: Rect, block: Block, text: &str) {
+    let paragraph = Paragraph::new(Span::from(text))
+        .block(block)
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: true });
+    f.render_widget(tui::widgets::Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+fn message_ui<B: Backend>(f: &mut Frame<B>, title: &str, text: &str) {
+    let size = f.size();
+
+    let paragraph = Paragraph::new(Span::from(text))
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, size);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{ok, serve, serve_once};
+
+    #[test]
+    fn category_labels_follow_the_tree() {
+        assert_eq!(category_label(None, None), None);
+        assert_eq!(category_label(Some("1"), None).unwrap(), "Anime");
+        assert_eq!(
+            category_label(Some("1"), Some("2")).unwrap(),
+            "Anime / English-translated"
+        );
+        assert_eq!(
+            category_lines(Some(4)),
+            ["All Pictures", "Graphics", "Photos"]
+        );
+    }
+
+    #[test]
+    fn category_list_starts_on_the_current_category() {
+        let choices = category_choices();
+        assert_eq!(choices[0], (None, None));
+        assert_eq!(choices[1], (Some("1"), None));
+        assert_eq!(choices[3], (Some("1"), Some("2")));
+        assert_eq!(
+            choices.len(),
+            1 + CATEGORIES.iter().map(|c| 1 + c.subs.len()).sum::<usize>()
+        );
+
+        let mut app = App::new();
+        let mut params = Params::new();
+        params.set_category(Some("1"), Some("2"));
+        assert!(matches!(
+            press(&mut app, &mut params, "c"),
+            Mode::Modal(Modal::CategoryList(state)) if state.selected() == Some(3)
+        ));
+    }
+
+    #[test]
+    fn parse_date_understands_api_formats() {
+        assert_eq!(parse_date("2022-10-15 12:34"), Some(1665837240));
+        assert_eq!(parse_date("2022-10-15 12:34:56"), Some(1665837296));
+        assert_eq!(
+            parse_date("Sat, 15 Oct 2022 12:34:56 -0000"),
+            Some(1665837296)
+        );
+        assert_eq!(parse_date("1665837296"), Some(1665837296));
+        assert_eq!(parse_date("1970-01-01 00:00"), Some(0));
+        assert_eq!(parse_date(""), None);
+        assert_eq!(parse_date("yesterday"), None);
+        assert_eq!(parse_date("Oct 15"), None);
+        // out of range numbers are rejected rather than overflowing
+        assert_eq!(parse_date("2022-10-15 99999999999999999"), None);
+        assert_eq!(parse_date("2022-10-15 12:60"), None);
+        assert_eq!(parse_date("99999999999999999-10-15 12:34"), None);
+    }
+
+    #[test]
+    fn freshness_buckets_by_age() {
+        let now = parse_date("2022-10-15 12:00").unwrap();
+        assert_eq!(freshness("2022-10-15 01:00", now), Some(Color::Green));
+        assert_eq!(freshness("2022-10-10 01:00", now), Some(Color::Yellow));
+        assert_eq!(freshness("2022-09-01 01:00", now), None);
+        assert_eq!(freshness("garbage", now), None);
+    }
+
+    #[test]
+    fn statuses_color_like_nyaa() {
+        assert_eq!(status_color("trusted"), Some(Color::Green));
+        assert_eq!(status_color("Remake"), Some(Color::Red));
+        assert_eq!(status_color("normal"), None);
+        assert_eq!(status_color(""), None);
+    }
+
+    #[test]
+    fn long_names_end_in_an_ellipsis() {
+        assert_eq!(ellipsize("short", 10), "short");
+        assert_eq!(ellipsize("exactly10!", 10), "exactly10!");
+        assert_eq!(ellipsize("a bit too long", 10), "a bit too…");
+        // wide characters take two cells
+        assert_eq!(ellipsize("進撃の巨人", 6), "進撃…");
+        assert_eq!(ellipsize("anything", 0), "");
+    }
+
+    #[test]
+    fn categories_are_shortened() {
+        assert_eq!(short_category("Anime", "English-translated"), "Anime/Eng");
+        assert_eq!(short_category("Software", "Applications"), "Soft/Apps");
+        assert_eq!(short_category("Audio", "Lossless"), "Audio/Lossless");
+        assert_eq!(short_category("Live Action", ""), "Live");
+        assert_eq!(short_category("", ""), "");
+    }
+
+    #[test]
+    fn seeder_counts_get_colors() {
+        assert_eq!(seeder_color("0", (1, 10)), Some(Color::Red));
+        assert_eq!(seeder_color("3", (1, 10)), Some(Color::Yellow));
+        assert_eq!(seeder_color("1,200", (1, 10)), Some(Color::Green));
+        assert_eq!(seeder_color("", (1, 10)), None);
+        assert_eq!(seeder_color("4", (5, 50)), Some(Color::Red));
+
+        assert_eq!(parse_seeder_colors(" 5, 50 "), Some((5, 50)));
+        assert_eq!(parse_seeder_colors("50,5"), None);
+        assert_eq!(parse_seeder_colors("5"), None);
+    }
+
+    #[test]
+    fn relative_dates_pick_the_largest_unit() {
+        let now = parse_date("2022-10-15 12:00").unwrap();
+        assert_eq!(relative_date("2022-10-15 11:59:30", now), "just now");
+        assert_eq!(relative_date("2022-10-15 11:15", now), "45m ago");
+        assert_eq!(relative_date("2022-10-15 09:00", now), "3h ago");
+        assert_eq!(relative_date("2022-10-12 12:00", now), "3d ago");
+        assert_eq!(relative_date("2022-07-01 00:00", now), "3mo ago");
+        assert_eq!(relative_date("2019-10-15 12:00", now), "3y ago");
+        // from the future, or not a date at all
+        assert_eq!(relative_date("2022-10-16 12:00", now), "just now");
+        assert_eq!(relative_date("soon", now), "soon");
+    }
+
+    fn args(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn parse_args_reads_batch_options() {
+        assert_eq!(args(&[]), Ok(Args::default()));
+        assert_eq!(
+            args(&[
+                "--query",
+                "one piece",
+                "--json",
+                "--sort",
+                "seeders",
+                "--limit",
+                "5"
+            ]),
+            Ok(Args {
+                query: Some("one piece".to_string()),
+                json: true,
+                sort: Some(SortKey::Seeders),
+                limit: Some(5),
+                ..Default::default()
+            })
+        );
+        let magnets = args(&["--magnet-only", "-q", "bleach"]).unwrap();
+        assert!(magnets.magnet_only && magnets.batch());
+        assert!(!args(&["-q", "bleach"]).unwrap().batch());
+        assert!(args(&["--limit"]).is_err());
+        assert!(args(&["--limit", "five"]).is_err());
+        assert!(args(&["--sort", "bytes"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn sanitize_filename_keeps_names_in_the_directory() {
+        assert_eq!(sanitize_filename("[Sub] Show - 01"), "[Sub] Show - 01");
+        assert_eq!(sanitize_filename("a/b\\c:d"), "a_b_c_d");
+        assert_eq!(sanitize_filename("../../etc"), "_.._etc");
+        assert_eq!(sanitize_filename(" .. "), "torrent");
+    }
+
+    #[test]
+    fn parse_filesize_handles_units() {
+        assert_eq!(parse_filesize("1.5 gib"), Some(1610612736));
+        assert_eq!(parse_filesize("2 Mb"), Some(2_000_000));
+        assert_eq!(parse_filesize("1 TiB"), Some(1 << 40));
+        assert_eq!(parse_filesize("1.2GiB"), parse_filesize("1.2 GiB"));
+        assert_eq!(parse_filesize("1,024 KiB"), Some(1 << 20));
+        // no unit means bytes
+        assert_eq!(parse_filesize("512"), Some(512));
+        assert_eq!(parse_filesize(" 7 bytes "), Some(7));
+        assert_eq!(parse_filesize("GiB"), None);
+        assert_eq!(parse_filesize("1.2.3 GiB"), None);
+        assert_eq!(parse_filesize("12 XB"), None);
+        assert_eq!(parse_filesize("-1 GiB"), None);
+    }
+
+    #[test]
+    fn zero_and_unknown_sizes_are_unknown() {
+        assert_eq!(parse_filesize("350.5 MiB"), Some(367525888));
+        assert_eq!(parse_filesize("0 B"), None);
+        assert_eq!(parse_filesize("0.0 KiB"), None);
+        assert_eq!(parse_filesize("Unknown"), None);
+        assert_eq!(parse_filesize(""), None);
+        assert_eq!(display_size("0 B"), "—");
+        assert_eq!(display_size("1.2 GiB"), "1.2 GiB");
+    }
+
+    #[test]
+    fn scrollbar_thumb_follows_the_selection() {
+        assert_eq!(scrollbar(5, 10, 0, 4), [false; 4]);
+        assert_eq!(
+            scrollbar(20, 5, 0, 8),
+            [true, true, false, false, false, false, false, false]
+        );
+        assert_eq!(
+            scrollbar(20, 5, 19, 8),
+            [false, false, false, false, false, false, true, true]
+        );
+        assert_eq!(
+            scrollbar(1000, 5, 500, 8).iter().filter(|on| **on).count(),
+            1
+        );
+
+        if std::env::var("NYAA_SCROLLBAR").is_err() {
+            assert!(scrollbar_enabled(&config::Config::default()));
+            assert!(!scrollbar_enabled(&config::Config::parse(
+                "scrollbar = false"
+            )));
+        }
+    }
+
+    #[test]
+    fn exact_sizes_are_grouped() {
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1288490188), "1,288,490,188");
+        assert_eq!(human_size(1288490188), "1.20 GiB");
+        assert_eq!(human_size(512), "512 B");
+
+        let mut item = item("1", "", "");
+        item.filesize = "1.2 GiB".to_string();
+        assert!(detail_text(&item, false).contains("size: 1.2 GiB\n"));
+        assert!(detail_text(&item, true).contains("size: 1,288,490,189 bytes (1.20 GiB)\n"));
+    }
+
+    #[test]
+    fn sizes_shorten_when_narrow() {
+        assert_eq!(fit_size("1.23 GiB", 8), "1.23 GiB");
+        assert_eq!(fit_size("1.23 GiB", 5), "1.2G");
+        assert_eq!(fit_size("512.6 MiB", 5), "513M");
+        assert_eq!(fit_size("1.0 KiB", 5), "1K");
+        assert_eq!(fit_size("0 B", 1), "—");
+    }
+
+    #[test]
+    fn unknown_sizes_sort_last_both_ways() {
+        let sized = |id: &str, size: &str| Response {
+            id: id.to_string(),
+            filesize: size.to_string(),
+            ..Default::default()
+        };
+        let mut app = App::new();
+        app.update_items(vec![
+            sized("1", "0 B"),
+            sized("2", "2 GiB"),
+            sized("3", "Unknown"),
+            sized("4", "10 MiB"),
+        ]);
+        let ids = |app: &App| app.items.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+
+        app.sort_by(SortKey::Size, true);
+        assert_eq!(ids(&app)[..2], ["4", "2"]);
+        app.sort_by(SortKey::Size, false);
+        assert_eq!(ids(&app)[..2], ["2", "4"]);
+    }
+
+    #[test]
+    fn magnet_commands_route_by_category() {
+        let mut app = App::new();
+        app.category_commands = parse_category_commands(
+            "Anime = aria2c {magnet}; Software / Games=qbt {magnet};bad;x=",
+        );
+        assert_eq!(app.category_commands.len(), 2);
+        assert_eq!(app.category_commands[1].0, "Software/Games");
+        if std::env::var("NYAA_CATEGORY_COMMANDS").is_err() {
+            let config = config::Config::parse(
+                "[category_commands]\n\"Software / Games\" = \"qbt {magnet}\"\nAnime = \" aria2c {magnet}\"\nx = \"\"",
+            );
+            assert_eq!(category_commands(&config), app.category_commands);
+        }
+
+        let mut anime = item("1", "", "");
+        anime.category = "anime".to_string();
+        assert_eq!(app.magnet_command_for(&anime), Some("aria2c {magnet}"));
+
+        let mut game = item("2", "", "");
+        game.category = "Software".to_string();
+        game.sub_category = "Games".to_string();
+        assert_eq!(app.magnet_command_for(&game), Some("qbt {magnet}"));
+
+        game.sub_category = "Applications".to_string();
+        assert_eq!(app.magnet_command_for(&game), None);
+        app.magnet_command = Some("fallback {magnet}".to_string());
+        assert_eq!(app.magnet_command_for(&game), Some("fallback {magnet}"));
+    }
+
+    #[test]
+    fn magnets_json_has_sizes_in_bytes() {
+        let mut one = item("1", "one", "");
+        one.magnet = "magnet:?xt=urn:btih:ab".to_string();
+        one.hash = "ab".to_string();
+        one.filesize = "1 KiB".to_string();
+        let two = item("2", "two", "");
+        let json: serde_json::Value = serde_json::from_str(&magnets_json(&[one, two])).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"name": "one", "magnet": "magnet:?xt=urn:btih:ab", "hash": "ab", "size_bytes": 1024},
+                {"name": "two", "magnet": "", "hash": "", "size_bytes": null},
+            ])
+        );
+    }
+
+    #[test]
+    fn commands_fill_in_every_placeholder() {
+        let mut one = item("12", "one", "5");
+        one.magnet = "magnet:?dn=it's".to_string();
+        one.torrent = "https://nyaa.si/download/12.torrent".to_string();
+        assert_eq!(
+            fill_command("client -a {magnet} {torrent} {url}", &one),
+            "client -a 'magnet:?dn=it'\\''s' 'https://nyaa.si/download/12.torrent' \
+             'https://nyaa.si/view/12'"
+        );
+
+        let config =
+            config::Config::parse("torrent_command = \"tr -a {torrent}\"\nmagnet_command = \" \"");
+        // nothing sets this variable, so the file decides
+        let command = command_setting(&config, "NYAA_TEST_UNSET", "torrent_command");
+        assert_eq!(command.as_deref(), Some("tr -a {torrent}"));
+        let command = command_setting(&config, "NYAA_TEST_UNSET", "magnet_command");
+        assert_eq!(command, None);
+    }
+
+    #[test]
+    fn api_url_prefers_the_environment_then_the_config() {
+        let config = config::Config::parse("api_url = \" http://localhost:8080 \"");
+        assert_eq!(api_url_for(None, &config), "http://localhost:8080");
+        assert_eq!(
+            api_url_for(None, &config::Config::default()),
+            nyaa::NYAA_URL
+        );
+        assert_eq!(
+            api_url_for(None, &config::Config::parse("api_url = \"\"")),
+            nyaa::NYAA_URL
+        );
+
+        assert_eq!(
+            api_url_for(Some("http://nyaa.lan"), &config),
+            "http://nyaa.lan"
+        );
+        assert_eq!(api_url_for(Some(" "), &config), "http://localhost:8080");
+    }
+
+    #[test]
+    fn download_dirs_come_from_the_config() {
+        let config = config::Config::parse("download_dir = \"~/torrents\"\nexport_dir = \"/srv\"");
+        if std::env::var_os("NYAA_DOWNLOAD_DIR").is_none() {
+            let home = dirs::home_dir().unwrap();
+            assert_eq!(download_dir(&config), home.join("torrents"));
+            let default = download_dir(&config::Config::default());
+            assert_eq!(default, default_download_dir());
+        }
+        // nothing sets this variable, so the file decides
+        let dir = dir_setting(&config, "NYAA_TEST_UNSET", "export_dir");
+        assert_eq!(dir, Some("/srv".into()));
+        let config = config::Config::parse("download_dir = \" \"");
+        assert_eq!(
+            dir_setting(&config, "NYAA_TEST_UNSET", "download_dir"),
+            None
+        );
+    }
+
+    #[test]
+    fn big_d_saves_the_torrent_once() {
+        let server = serve(|stream, _, _| stream.write_all(&ok("", b"d8:announce0:e")));
+        let dir = std::env::temp_dir().join(format!("nyaa-download-{}", std::process::id()));
+        let mut app = App::new();
+        app.download_dir = dir.clone();
+        let mut one = item("1", "one", "5");
+        one.torrent = format!("{}/1.torrent", server.url);
+        app.update_items(vec![one]);
+        let mut params = Params::new();
+
+        let mode = press(&mut app, &mut params, "D");
+        assert!(matches!(mode, Mode::Modal(Modal::Message(_))));
+        let path = dir.join("one.torrent");
+        assert_eq!(std::fs::read(&path).unwrap(), b"d8:announce0:e");
+        // a repeat straight after is skipped by the launch cooldown
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(press(&mut app, &mut params, "D"), Mode::Normal));
+        assert!(!path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_downloads_are_errors() {
+        let mut app = App::new();
+        app.client = nyaa::Client::with_base("http://127.0.0.1:1").retries(0);
+        let mut one = item("1", "one", "5");
+        // nothing listens on port 1
+        one.torrent = "http://127.0.0.1:1/1.torrent".to_string();
+        app.update_items(vec![one]);
+        let mut params = Params::new();
+        let mode = press(&mut app, &mut params, "a");
+        assert!(matches!(mode, Mode::Modal(Modal::Error(_))));
+    }
+
+    #[test]
+    fn download_notes_show_progress() {
+        assert_eq!(
+            download_note(512, Some(2048), 0),
+            "downloading… 512 B of 2.00 KiB (25%)"
+        );
+        assert_eq!(download_note(0, Some(0), 0), "| downloading… 0 B");
+        assert_eq!(download_note(1536, None, 1), "/ downloading… 1.50 KiB");
+        assert_eq!(download_note(1536, None, 5), "/ downloading… 1.50 KiB");
+    }
+
+    #[test]
+    fn exports_name_their_files() {
+        let dir = std::env::temp_dir().join(format!("nyaa-export-{}", std::process::id()));
+        let items = vec![item("1", "one", "5"), item("2", "two", "0")];
+        let path = export_items(&items, &dir, "", 1234).unwrap();
+        assert_eq!(path, dir.join("nyaa-1234.json"));
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(serde_json::from_str::<Responses>(&json).unwrap(), items);
+
+        let path = export_items(&items, &dir, " season/one ", 0).unwrap();
+        assert_eq!(path, dir.join("season_one.json"));
+        let path = export_items(&[], &dir, "empty.json", 0).unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "[]");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn opener_can_be_overridden() {
+        let (program, args) = opener_for(Some(" firefox  --new-tab "));
+        assert_eq!(
+            (program.as_str(), args),
+            ("firefox", vec!["--new-tab".to_string()])
+        );
+
+        let default = if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(windows) {
+            "rundll32"
+        } else {
+            "xdg-open"
+        };
+        assert_eq!(opener_for(None).0, default);
+        assert_eq!(opener_for(Some("  ")).0, default);
+    }
+
+    #[test]
+    fn shell_quote_survives_quotes() {
+        assert_eq!(shell_quote("magnet:?dn=a b"), "'magnet:?dn=a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn latency_summary_uses_nearest_rank() {
+        assert_eq!(latency_summary(vec![]), None);
+        assert_eq!(latency_summary(vec![5.0]), Some([5.0, 5.0, 5.0, 5.0]));
+        let millis = (1..=20).rev().map(f64::from).collect();
+        assert_eq!(latency_summary(millis), Some([1.0, 10.0, 19.0, 20.0]));
+    }
+
+    #[test]
+    fn parse_import_reads_ids_urls_and_magnets() {
+        let text = "1234\n\
+            https://nyaa.si/view/5678/\n\
+            \n\
+            magnet:?xt=urn:btih:abcdef&dn=Some%20Show\n\
+            not a torrent\n";
+        let (items, skipped) = parse_import(text);
+        assert_eq!(skipped, 1);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].id, "1234");
+        assert_eq!(items[0].torrent, "https://nyaa.si/download/1234.torrent");
+        assert_eq!(items[1].id, "5678");
+        assert_eq!(items[2].name, "Some Show");
+        assert_eq!(items[2].hash, "abcdef");
+        assert_eq!(items[2].id, "");
+    }
+
+    #[test]
+    fn aria2_input_puts_items_in_category_dirs() {
+        let items = [
+            Response {
+                magnet: "magnet:?xt=urn:btih:a".to_string(),
+                category: "Anime".to_string(),
+                sub_category: "Raw".to_string(),
+                ..Default::default()
+            },
+            Response {
+                magnet: "magnet:?xt=urn:btih:b".to_string(),
+                ..Default::default()
+            },
+            Response::default(),
+        ];
+        assert_eq!(
+            aria2_input(&items, Some(std::path::Path::new("/dl"))),
+            "magnet:?xt=urn:btih:a\n  dir=/dl/Anime - Raw\nmagnet:?xt=urn:btih:b\n"
+        );
+        assert_eq!(
+            aria2_input(&items, None),
+            "magnet:?xt=urn:btih:a\nmagnet:?xt=urn:btih:b\n"
+        );
+
+        // a download directory that can't be made is an error
+        let file = std::env::temp_dir().join(format!("nyaa-aria2-{}", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        let mut app = App::new();
+        app.download_dir = file.join("dl");
+        app.update_items(items.to_vec());
+        let mode = press(&mut app, &mut Params::new(), "A");
+        assert!(matches!(mode, Mode::Modal(Modal::Error(_))));
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn fuzzy_filter_narrows_the_page() {
+        assert_eq!(fuzzy_match("One Piece", "opc"), Some(vec![0, 4, 7]));
+        assert_eq!(fuzzy_match("One Piece", "o p"), Some(vec![0, 4]));
+        assert_eq!(fuzzy_match("One Piece", "cp"), None);
+        assert_eq!(fuzzy_match("anything", ""), Some(vec![]));
+
+        let mut app = App::new();
+        let mut params = Params::new();
+        let names = ["One Piece 1080p", "Bleach 720p", "One Punch Man"];
+        app.update_items(names.iter().map(|n| item(n, n, "1")).collect());
+        let mode = press(&mut app, &mut params, "&op1");
+        assert!(matches!(mode, Mode::Input(Input::Fuzzy, _, 3)));
+        assert_eq!(app.items.len(), 1);
+        assert_eq!(app.items[0].name, "One Piece 1080p");
+        assert!(status_line(&app, &params).ends_with("| matching \"op1\""));
+
+        // enter keeps the filter, esc in the table takes it off
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Enter, &mut app, &mut params));
+        assert!(matches!(mode.unwrap(), Some(Mode::Normal)));
+        assert_eq!(app.items.len(), 1);
+        let mode = runtime.block_on(handle_key(
+            Mode::Normal,
+            KeyCode::Esc,
+            &mut app,
+            &mut params,
+        ));
+        assert!(matches!(mode.unwrap(), Some(Mode::Normal)));
+        assert_eq!(app.items.len(), 3);
+
+        // esc in the prompt does too
+        let mode = press(&mut app, &mut params, "&bl");
+        assert_eq!(app.items.len(), 1);
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Esc, &mut app, &mut params));
+        assert!(matches!(mode.unwrap(), Some(Mode::Normal)));
+        assert_eq!(app.items.len(), 3);
+    }
+
+    #[test]
+    fn highlight_matches_merges_overlaps() {
+        let text = |spans: Vec<Span>| {
+            spans
+                .into_iter()
+                .map(|s| {
+                    if s.style.add_modifier.contains(Modifier::BOLD) {
+                        format!("[{}]", s.content)
+                    } else {
+                        s.content.into_owned()
+                    }
+                })
+                .collect::<String>()
+        };
+        assert_eq!(
+            text(highlight_matches("One Piece 1080p", "piece one", "")),
+            "[One] [Piece] 1080p"
+        );
+        assert_eq!(
+            text(highlight_matches("abcdef", "bcd cde ab", "")),
+            "[abcde]f"
+        );
+        assert_eq!(text(highlight_matches("Bleach", "", "")), "Bleach");
+        assert_eq!(text(highlight_matches("", "x", "")), "");
+        assert_eq!(
+            text(highlight_matches("Bleach 01", "", "bch1")),
+            "[B]lea[ch] 0[1]"
+        );
+        assert_eq!(text(highlight_matches("Bleach", "", "xyz")), "Bleach");
+    }
+
+    #[test]
+    fn magnet_trackers_are_decoded() {
+        let magnet = "magnet:?xt=urn:btih:abc&dn=x\
+            &tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce\
+            &tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce";
+        assert_eq!(
+            magnet_trackers(magnet),
+            [
+                "http://nyaa.tracker.wf:7777/announce",
+                "udp://open.stealth.si:80/announce"
+            ]
+        );
+        assert!(magnet_trackers("not a magnet").is_empty());
+    }
+
+    fn item(id: &str, name: &str, seeders: &str) -> Response {
+        Response {
+            id: id.to_string(),
+            name: name.to_string(),
+            seeders: seeders.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sort_by_column_toggles_and_skips_unsortable() {
+        let mut app = App::new();
+        app.update_items(vec![
+            item("1", "b", "10"),
+            item("2", "a", "1,000"),
+            item("3", "c", "5"),
+        ]);
+        let ids = |app: &App| app.items.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+
+        app.sort_by_column(4);
+        assert_eq!(ids(&app), ["2", "1", "3"]);
+        app.sort_by_column(4);
+        assert_eq!(ids(&app), ["3", "1", "2"]);
+
+        app.sort_by_column(1);
+        assert_eq!(ids(&app), ["2", "1", "3"]);
+
+        app.sort_by_column(0);
+        assert_eq!(app.sort, Some((SortKey::Name, true)));
+    }
+
+    #[test]
+    fn bookmarks_toggle_and_replace_the_page() {
+        let mut app = App::new();
+        app.saving = false;
+        let mut params = Params::new();
+        app.update_items(vec![item("1", "a", ""), item("2", "b", "")]);
+        press(&mut app, &mut params, "jf");
+        assert!(app.is_bookmarked("1"));
+        assert!(!app.is_bookmarked("2"));
+
+        press(&mut app, &mut params, "F");
+        assert_eq!(app.items.len(), 1);
+        assert_eq!(app.items[0].id, "1");
+        // unstarring in the bookmark view drops it from the view too
+        press(&mut app, &mut params, "f");
+        assert!(app.bookmarks.is_empty());
+        assert!(app.items.is_empty());
+
+        press(&mut app, &mut params, "F");
+        assert_eq!(app.items.len(), 2);
+    }
+
+    #[test]
+    fn sort_cycles_through_every_key() {
+        let mut app = App::new();
+        app.update_items(vec![item("1", "b", "5"), item("2", "a", "9")]);
+        app.cycle_sort();
+        assert_eq!(app.sort, Some((SortKey::Name, true)));
+        assert_eq!(app.items[0].name, "a");
+        for _ in 0..3 {
+            app.cycle_sort();
+        }
+        assert_eq!(app.sort, Some((SortKey::Seeders, false)));
+        assert_eq!(app.items[0].seeders, "9");
+        app.cycle_sort();
+        app.cycle_sort();
+        assert_eq!(app.sort, Some((SortKey::Name, true)));
+    }
+
+    #[test]
+    fn reverse_keeps_the_selected_item() {
+        let mut app = App::new();
+        app.update_items(vec![
+            item("1", "", ""),
+            item("2", "", ""),
+            item("3", "", ""),
+        ]);
+        app.first_item();
+        app.reverse();
+        assert_eq!(app.current, Some(2));
+        assert_eq!(app.state.selected(), Some(2));
+        assert_eq!(app.items[2].id, "1");
+
+        app.sort_by(SortKey::Date, true);
+        app.reverse();
+        assert_eq!(app.sort, Some((SortKey::Date, false)));
+    }
+
+    #[test]
+    fn actions_on_an_empty_list_do_nothing() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        app.update_items(vec![]);
+        // a selection left over from a longer list shouldn't reach past the end
+        app.current = Some(3);
+        for key in [
+            "o", "m", "t", "a", "D", "s", "T", "d", " ", "y", "I", "N", "f",
+        ] {
+            assert!(
+                matches!(
+                    press(&mut app, &mut params, key),
+                    Mode::Modal(Modal::Message(_))
+                ),
+                "{}",
+                key
+            );
+        }
+        assert!(app.viewed.is_empty());
+        assert!(app.marked.is_empty());
+        assert!(app.launched.is_none());
+        assert!(app.bookmarks.is_empty());
+    }
+
+    #[test]
+    fn log_keeps_recent_entries_and_messages() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        for i in 0..LOG_LEN + 5 {
+            app.log(i.to_string());
+        }
+        assert_eq!(app.log.len(), LOG_LEN);
+        assert_eq!(app.log[0].1, "5");
+
+        press(&mut app, &mut params, "o");
+        assert_eq!(app.log.back().unwrap().1, "Nothing selected.");
+        assert!(matches!(
+            press(&mut app, &mut params, "L"),
+            Mode::Modal(Modal::Log(state)) if state.selected() == Some(LOG_LEN - 1)
+        ));
+        assert_eq!(clock(86400 + 3661), "01:01:01");
+    }
+
+    #[test]
+    fn state_reads_old_and_new_formats() {
+        assert_eq!(parse_state("1234\n").last_id, 1234);
+        assert_eq!(parse_state("1234\n").last_query, "");
+        let state = State {
+            last_id: 99,
+            viewed: BTreeMap::from([(3, true), (120, false)]),
+            last_query: "one piece".to_string(),
+            history: vec!["one piece".to_string()],
+        };
+        assert_eq!(parse_state(&serde_json::to_string(&state).unwrap()), state);
+        assert_eq!(parse_state(r#"{"last_id": 5}"#).last_id, 5);
+        assert_eq!(parse_state("garbage"), State::default());
+    }
+
+    #[test]
+    fn next_page_comes_from_the_prefetch() {
+        let body = serde_json::to_string(&vec![item("99", "prefetched", "1")]).unwrap();
+        let mut app = App::new();
+        app.saving = false;
+        // only one request is answered, a second would fail
+        app.client = nyaa::Client::with_base(&serve_once(body))
+            .retries(0)
+            .timeout(Duration::from_millis(500));
+        app.warm = true;
+        app.update_items((0..20).map(|i| item(&i.to_string(), "", "")).collect());
+        let mut params = Params::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut key = |app: &mut App, c| {
+            let mode = handle_key(Mode::Normal, KeyCode::Char(c), app, &mut params);
+            runtime.block_on(mode).unwrap().unwrap()
+        };
+        key(&mut app, 'g');
+        assert!(app.prefetch.is_none());
+        key(&mut app, 'G');
+        assert!(matches!(&app.prefetch, Some((next, ..)) if next.page == 2));
+        let mode = key(&mut app, 'n');
+        assert!(matches!(mode, Mode::Normal));
+        assert_eq!(params.page, 2);
+        assert_eq!(app.items[0].name, "prefetched");
+
+        // nor is one that's been sitting around
+        app.drop_prefetch();
+        app.current = Some(0);
+        runtime.block_on(async { app.prefetch(&params) });
+        assert!(matches!(&app.prefetch, Some((next, ..)) if next.page == 3));
+        let mut next = params.clone();
+        next.page = 3;
+        let later = Instant::now() + PREFETCH_MAX_AGE;
+        assert!(app.take_prefetch(&next, later).is_none());
+        assert!(app.prefetch.is_none());
+
+        // a different search has no use for it
+        runtime.block_on(async { app.prefetch(&params) });
+        params.set_query("other");
+        params.page = 3;
+        assert!(app.take_prefetch(&params, Instant::now()).is_none());
+        assert!(app.prefetch.is_none());
+    }
+
+    #[test]
+    fn quitting_can_ask_first() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut key = |app: &mut App, mode, c| {
+            let mode = handle_key(mode, KeyCode::Char(c), app, &mut params);
+            runtime.block_on(mode).unwrap()
+        };
+        assert!(key(&mut app, Mode::Normal, 'q').is_none());
+        // a pending count eats the q
+        let count = Mode::PendingCount("5".to_string());
+        assert!(matches!(key(&mut app, count, 'q'), Some(Mode::Normal)));
+
+        app.confirm_quit = true;
+        let mode = key(&mut app, Mode::Normal, 'q').unwrap();
+        assert!(matches!(mode, Mode::Modal(Modal::ConfirmQuit)));
+        assert!(matches!(key(&mut app, mode, 'n'), Some(Mode::Normal)));
+        assert!(key(&mut app, Mode::Modal(Modal::ConfirmQuit), 'y').is_none());
+
+        let config = config::Config::parse("confirm_quit = true");
+        if std::env::var("NYAA_CONFIRM_QUIT").is_err() {
+            assert!(confirm_quit_enabled(&config));
+            assert!(!confirm_quit_enabled(&config::Config::default()));
+        }
+    }
+
+    #[test]
+    fn numbers_come_from_the_environment_then_the_config() {
+        let config = config::Config::parse("retries = \" 4 \"\ntimeout = soon");
+        assert_eq!(
+            parsed_setting(&config, "NYAA_TEST_UNSET", "retries"),
+            Some(4)
+        );
+        assert_eq!(
+            parsed_setting::<f64>(&config, "NYAA_TEST_UNSET", "timeout"),
+            None
+        );
+        assert_eq!(
+            parsed_setting::<u32>(&config, "NYAA_TEST_UNSET", "missing"),
+            None
+        );
+        if std::env::var("NYAA_CONFIRM_MARK").is_err() {
+            let config = config::Config::parse("confirm_mark = 3");
+            assert_eq!(confirm_mark_threshold(&config), 3);
+            assert_eq!(confirm_mark_threshold(&config::Config::default()), 10);
+        }
+        if std::env::var("NYAA_MAX_FPS").is_err() {
+            let config = config::Config::parse("max_fps = 0");
+            assert_eq!(frame_interval(&config), Duration::ZERO);
+            let config = config::Config::parse("max_fps = 10");
+            assert_eq!(frame_interval(&config), Duration::from_millis(100));
+        }
+        if std::env::var("NYAA_LAUNCH_COOLDOWN_MS").is_err() {
+            let config = config::Config::parse("launch_cooldown_ms = 50");
+            assert_eq!(launch_cooldown(&config), Duration::from_millis(50));
+            let config = config::Config::default();
+            assert_eq!(launch_cooldown(&config), Duration::from_millis(300));
+        }
+        if std::env::var("NYAA_RATE_LIMIT").is_err() {
+            assert_eq!(rate_limit(&config::Config::parse("rate_limit = 0.5")), 0.5);
+            let config = config::Config::parse("rate_limit = inf");
+            assert_eq!(rate_limit(&config), nyaa::DEFAULT_RATE_LIMIT);
+        }
+    }
+
+    #[test]
+    fn compression_is_on_unless_turned_off() {
+        if std::env::var("NYAA_COMPRESSION").is_err() {
+            assert!(compression_enabled(&config::Config::default()));
+            let config = config::Config::parse("compression = false");
+            assert!(!compression_enabled(&config));
+            let config = config::Config::parse("compression = \"yes\"");
+            assert!(compression_enabled(&config));
+        }
+    }
+
+    #[test]
+    fn row_window_follows_the_selection() {
+        // 23 lines fit 6 rows, the last without its blank line
+        assert_eq!(row_window(0, Some(0), 20, 23), (0, 6));
+        assert_eq!(row_window(0, Some(5), 20, 23), (0, 6));
+        // scrolling down counts every blank line, so one less fits
+        assert_eq!(row_window(0, Some(6), 20, 23), (2, 7));
+        assert_eq!(row_window(2, Some(1), 20, 23), (1, 6));
+        assert_eq!(row_window(9, None, 20, 23), (0, 6));
+        assert_eq!(row_window(0, Some(0), 2, 23), (0, 2));
+        assert_eq!(row_window(0, None, 0, 23), (0, 0));
+    }
+
+    #[test]
+    fn clicks_select_the_row_under_them() {
+        let mut app = App::new();
+        let params = Params::new();
+        let items = (0..20).map(|i| item(&i.to_string(), &format!("name{}", i), ""));
+        app.update_items(items.collect());
+        app.first_item();
+        let mut terminal = Terminal::new(tui::backend::TestBackend::new(80, 30)).unwrap();
+        terminal.draw(|f| ui(f, &mut app, &params, "")).unwrap();
+        let line = |terminal: &Terminal<tui::backend::TestBackend>, y| {
+            let buffer = terminal.backend().buffer();
+            (0..80)
+                .map(|x| buffer.get(x, y).symbol.clone())
+                .collect::<String>()
+        };
+        assert!(line(&terminal, 12).contains("name2 "));
+        // rows start under the margin, the border and the header, 4 lines apart
+        let click = |x, y| MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: x,
+            row: y,
+            modifiers: crossterm::event::KeyModifiers::NONE,
+        };
+        let start = Instant::now();
+        mouse_event(&mut app, click(10, 12), start);
+        assert_eq!(app.current, Some(2));
+        // the header, the blank line between rows and the border do nothing
+        for (x, y) in [(10, 2), (10, 7), (0, 12), (10, 28)] {
+            mouse_event(&mut app, click(x, y), start);
+            assert_eq!(app.current, Some(2));
+        }
+
+        // the table scrolls along with the selection
+        app.select_index(10);
+        terminal.draw(|f| ui(f, &mut app, &params, "")).unwrap();
+        assert!(line(&terminal, 4).contains("name6 "));
+        mouse_event(&mut app, click(10, 4), start);
+        assert_eq!(app.current, Some(6));
+        assert_eq!(app.state.selected(), Some(6));
+
+        let later = start + Duration::from_millis(100);
+        let mode = mouse_event(&mut app, click(10, 5), later);
+        assert!(matches!(mode, Mode::Modal(Modal::Detail)));
+        let much_later = later + DOUBLE_CLICK;
+        mouse_event(&mut app, click(10, 4), much_later);
+        let mode = mouse_event(&mut app, click(10, 4), much_later + DOUBLE_CLICK);
+        assert!(matches!(mode, Mode::Normal));
+
+        let scroll = MouseEvent {
+            kind: MouseEventKind::ScrollDown,
+            ..click(0, 0)
+        };
+        mouse_event(&mut app, scroll, much_later);
+        assert_eq!(app.current, Some(7));
+    }
+
+    #[test]
+    fn magnets_are_shown_before_opening() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        let mut one = item("1", "one", "5");
+        one.magnet = "magnet:?xt=urn:btih:abc".to_string();
+        app.update_items(vec![one]);
+        let mode = press(&mut app, &mut params, "gm");
+        assert!(matches!(&mode, Mode::Modal(Modal::Magnet(item)) if item.magnet.ends_with("abc")));
+        // other keys leave it up, q closes it without opening anything
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Char('j'), &mut app, &mut params));
+        let mode = mode.unwrap().unwrap();
+        assert!(matches!(mode, Mode::Modal(Modal::Magnet(_))));
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Esc, &mut app, &mut params));
+        assert!(matches!(mode.unwrap().unwrap(), Mode::Normal));
+        assert!(app.launched.is_none());
+    }
+
+    #[test]
+    fn history_is_deduped_and_capped() {
+        let mut app = App::new();
+        app.saving = false;
+        for query in ["a", "b", " a ", "", "c"] {
+            app.remember_search(query);
+        }
+        assert_eq!(app.saved.history, ["c", "a", "b"]);
+        for i in 0..30 {
+            app.remember_search(&i.to_string());
+        }
+        assert_eq!(app.saved.history.len(), HISTORY_LEN);
+        assert_eq!(app.saved.history[0], "29");
+
+        // picking an entry searches for it again and moves it to the top
+        let body = serde_json::to_string(&vec![item("1", "", "")]).unwrap();
+        app.client = nyaa::Client::with_base(&serve_once(body)).retries(0);
+        let mut params = Params::new();
+        assert!(matches!(
+            press(&mut app, &mut params, "Hjj"),
+            Mode::Modal(Modal::History(ref state)) if state.selected() == Some(2)
+        ));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mode = Mode::Modal(Modal::History(first_selected(HISTORY_LEN)));
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Char('j'), &mut app, &mut params));
+        let mode = runtime.block_on(handle_key(
+            mode.unwrap().unwrap(),
+            KeyCode::Enter,
+            &mut app,
+            &mut params,
+        ));
+        assert!(matches!(mode.unwrap(), Some(Mode::Normal)));
+        assert_eq!(app.saved.history[0], "28");
+    }
+
+    #[test]
+    fn paging_past_either_end_says_so() {
+        let mut app = App::new();
+        // a fetch would fail with an error rather than a message
+        app.client = nyaa::Client::with_base("http://127.0.0.1:1").retries(0);
+        let mut params = Params::new();
+        let mode = press(&mut app, &mut params, "p");
+        assert!(matches!(mode, Mode::Modal(Modal::Message(m)) if m.contains("first page")));
+        params.page = nyaa::MAX_PAGE;
+        let mode = press(&mut app, &mut params, "5n");
+        assert!(matches!(mode, Mode::Modal(Modal::Message(m)) if m.contains("last page")));
+        assert_eq!(params.page, nyaa::MAX_PAGE);
+    }
+
+    #[test]
+    fn fetches_share_one_connection() {
+        let page = serde_json::to_vec(&vec![item("1", "one", "5")]).unwrap();
+        let server = serve(move |stream, _, _| stream.write_all(&ok("", &page)));
+        let mut app = App::new();
+        app.saving = false;
+        app.client = nyaa::Client::with_base(&server.url);
+        let mut params = Params::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(fetch_items(&mut app, &params)).unwrap();
+        params.next_page_by(1);
+        runtime.block_on(fetch_items(&mut app, &params)).unwrap();
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[test]
+    fn session_only_marks_are_not_saved() {
+        let mut app = App::new();
+        app.saving = false;
+        app.update_items(vec![item("1", "one", "5"), item("2", "two", "5")]);
+        let mut params = Params::new();
+        press(&mut app, &mut params, "es");
+        assert!(app.is_viewed_id(1));
+        assert!(app.saved.viewed.is_empty());
+
+        // switching back drops them, and s saves again
+        press(&mut app, &mut params, "e");
+        assert!(!app.is_viewed_id(1));
+        press(&mut app, &mut params, "s");
+        assert_eq!(app.saved.viewed, BTreeMap::from([(1, true)]));
+        assert_eq!(app.viewed, app.saved.viewed);
+    }
+
+    #[test]
+    fn failed_fetches_keep_the_old_results() {
+        let mut app = App::new();
+        app.saving = false;
+        // nothing listens on port 1
+        app.client = nyaa::Client::with_base("http://127.0.0.1:1").retries(0);
+        app.update_items(vec![item("1", "", "")]);
+        let mut params = Params::new();
+        params.set_query("one piece");
+        let mode = press(&mut app, &mut params, "n");
+        assert!(matches!(mode, Mode::Modal(Modal::Error(_))));
+        assert_eq!(params.page, 1);
+        assert_eq!(app.items.len(), 1);
+
+        // the error is logged, and any key takes it down again
+        assert!(app.log.back().unwrap().1.starts_with("network error"));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let next = handle_key(mode, KeyCode::Char('x'), &mut app, &mut params);
+        let mode = runtime.block_on(next).unwrap().unwrap();
+        assert!(matches!(mode, Mode::Normal));
+
+        // a search or b that fails leaves the query as it was too
+        let search = submit_input(Input::Search, "bleach".to_string(), &mut app, &mut params);
+        assert!(matches!(
+            runtime.block_on(search),
+            Mode::Modal(Modal::Error(_))
+        ));
+        assert!(matches!(
+            press(&mut app, &mut params, "b"),
+            Mode::Modal(Modal::Error(_))
+        ));
+        assert_eq!(params.query, "one piece");
+        assert_eq!(app.items.len(), 1);
+        // and a failed search stays out of the history
+        assert!(app.saved.history.is_empty());
+    }
+
+    #[test]
+    fn esc_undoes_a_live_search() {
+        let mut app = App::new();
+        app.saving = false;
+        app.live_search = true;
+        app.update_items(vec![item("1", "before", "")]);
+        let mut params = Params::new();
+        press(&mut app, &mut params, "/");
+        // as if typing "x" had fetched
+        params.set_query("x");
+        app.update_items(vec![item("2", "during", "")]);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let input = Mode::Input(Input::Search, "x".to_string(), 1);
+        let mode = runtime.block_on(handle_key(input, KeyCode::Esc, &mut app, &mut params));
+        assert!(matches!(mode.unwrap(), Some(Mode::Normal)));
+        assert_eq!(params.query, "");
+        assert_eq!(app.items[0].name, "before");
+        assert!(app.before_search.is_none());
+    }
+
+    #[test]
+    fn failed_reloads_keep_the_selection() {
+        let mut app = App::new();
+        app.client = nyaa::Client::with_base("http://127.0.0.1:1").retries(0);
+        app.update_items(vec![item("1", "", ""), item("2", "", "")]);
+        let mut params = Params::new();
+        let mode = press(&mut app, &mut params, "GR");
+        assert!(matches!(mode, Mode::Modal(Modal::Error(_))));
+        assert_eq!(app.current, Some(1));
+        assert_eq!(app.items.len(), 2);
+    }
+
+    #[test]
+    fn new_pages_start_at_the_top() {
+        let mut app = App::new();
+        let page = |n: usize| (0..n).map(|i| item(&i.to_string(), "", "")).collect();
+        app.update_items(page(10));
+        app.select_index(7);
+
+        // the same page again keeps the spot
+        app.show_page(page(10), false);
+        assert_eq!(app.current, Some(7));
+        // fewer results or another page go back to the top
+        app.show_page(page(9), false);
+        assert_eq!(app.current, Some(0));
+        app.select_index(7);
+        app.show_page(page(10), true);
+        assert_eq!(app.current, Some(0));
+        app.show_page(vec![], true);
+        assert_eq!(app.current, None);
+        assert_eq!(app.state.selected(), None);
+    }
+
+    #[test]
+    fn page_prompt_fetches_that_page() {
+        let mut app = App::new();
+        app.client = nyaa::Client::with_base("http://127.0.0.1:1").retries(0);
+        let mut params = Params::new();
+        assert!(matches!(
+            press(&mut app, &mut params, ":42"),
+            Mode::Input(Input::Page, text, 2) if text == "42"
+        ));
+        // nothing is listening, so the page goes back once the fetch fails
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        for page in ["4000", "70000", "99999999999"] {
+            let submit = submit_input(Input::Page, page.to_string(), &mut app, &mut params);
+            let mode = runtime.block_on(submit);
+            assert!(matches!(mode, Mode::Modal(Modal::Error(e)) if e.contains("p=1000&")));
+            assert_eq!(params.page, 1);
+        }
+
+        let mode = runtime.block_on(submit_input(
+            Input::Page,
+            "x".to_string(),
+            &mut app,
+            &mut params,
+        ));
+        assert!(matches!(mode, Mode::Modal(Modal::Message(m)) if m == "Not a page number: x"));
+    }
+
+    #[test]
+    fn failed_writes_turn_saving_off() {
+        let mut app = App::new();
+        app.persist(|| Err(io::Error::other("read-only file system")));
+        assert!(!app.saving);
+        assert_eq!(app.log.len(), 1);
+
+        let mut wrote = false;
+        app.persist(|| {
+            wrote = true;
+            Ok(())
+        });
+        assert!(!wrote);
+        assert_eq!(app.log.len(), 1);
+
+        // marks still work for the session
+        app.set_viewed(&[7], true);
+        assert!(app.is_viewed(&item("7", "", "")));
+    }
+
+    #[test]
+    fn terminal_title_shows_query_and_page() {
+        let mut params = Params::new();
+        assert_eq!(terminal_title(&params), "nyaa (p1)");
+        params.set_query("one piece");
+        params.next_page_by(2);
+        assert_eq!(terminal_title(&params), "nyaa: one piece (p3)");
+    }
+
+    #[test]
+    fn first_unviewed_skips_viewed_items() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        app.update_items(vec![
+            item("3", "", ""),
+            item("4", "", ""),
+            item("5", "", ""),
+        ]);
+        app.last_id = 2;
+        assert!(app.first_unviewed());
+        assert_eq!(app.current, Some(0));
+
+        app.last_id = 4;
+        assert!(app.first_unviewed());
+        assert_eq!(app.current, Some(2));
+
+        app.last_id = 5;
+        app.current = Some(1);
+        assert!(!app.first_unviewed());
+        assert_eq!(app.current, Some(1));
+        assert!(matches!(
+            press(&mut app, &mut params, "U"),
+            Mode::Modal(Modal::Message(_))
+        ));
+    }
+
+    #[test]
+    fn next_unviewed_goes_round() {
+        let mut app = App::new();
+        app.saving = false;
+        let mut params = Params::new();
+        app.update_items((1..=5).map(|i| item(&i.to_string(), "", "")).collect());
+        app.set_viewed(&[2, 3], true);
+        app.current = Some(0);
+        assert!(app.next_unviewed());
+        assert_eq!(app.current, Some(3));
+        press(&mut app, &mut params, "]");
+        assert_eq!(app.current, Some(4));
+        // past the end it starts over from the top
+        press(&mut app, &mut params, "]");
+        assert_eq!(app.current, Some(0));
+        // with nothing selected the first one counts
+        app.current = None;
+        assert!(app.next_unviewed());
+        assert_eq!(app.current, Some(0));
+
+        app.set_viewed(&[1, 4, 5], true);
+        assert!(matches!(
+            press(&mut app, &mut params, "]"),
+            Mode::Modal(Modal::Message(_))
+        ));
+        assert_eq!(app.current, Some(0));
+        app.update_items(vec![]);
+        assert!(!app.next_unviewed());
+    }
+
+    #[test]
+    fn default_sort_applies_until_one_is_picked() {
+        assert_eq!(
+            parse_sort_setting("seeders"),
+            Some((SortKey::Seeders, false))
+        );
+        assert_eq!(
+            parse_sort_setting(" Size:asc "),
+            Some((SortKey::Size, true))
+        );
+        assert_eq!(parse_sort_setting("size:sideways"), None);
+        assert_eq!(parse_sort_setting("peers"), None);
+        if std::env::var("NYAA_DEFAULT_SORT").is_err() {
+            let config = config::Config::parse("default_sort = \"size:asc\"");
+            assert_eq!(default_sort(&config), Some((SortKey::Size, true)));
+            assert_eq!(default_sort(&config::Config::default()), None);
+        }
+
+        let mut app = App::new();
+        app.default_sort = Some((SortKey::Seeders, false));
+        app.update_items(vec![item("1", "b", "1"), item("2", "a", "9")]);
+        assert_eq!(app.items[0].id, "2");
+
+        app.sort_by(SortKey::Name, true);
+        app.update_items(vec![item("3", "d", "9"), item("4", "c", "1")]);
+        assert_eq!(app.items[0].name, "c");
+    }
+
+    #[test]
+    fn min_seeders_hides_small_swarms() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        app.update_items(vec![
+            item("1", "", "3"),
+            item("2", "", "12"),
+            item("3", "", "n/a"),
+            item("4", "", "1,500"),
+        ]);
+        app.current = Some(1);
+        app.set_min_seeders(Some(10));
+        let ids: Vec<&str> = app.items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["2", "4"]);
+        assert_eq!(app.selected_id().as_deref(), Some("2"));
+
+        // a refetch is filtered too
+        app.update_items(vec![item("5", "", "9"), item("6", "", "10")]);
+        assert_eq!(app.items.len(), 1);
+
+        assert!(matches!(
+            press(&mut app, &mut params, "M"),
+            Mode::Input(Input::MinSeeders, text, 2) if text == "10"
+        ));
+        let mode = handle_key(
+            Mode::Input(Input::MinSeeders, "0".to_string(), 1),
+            KeyCode::Enter,
+            &mut app,
+            &mut params,
+        );
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(mode)
+            .unwrap();
+        assert_eq!(app.min_seeders, None);
+        assert_eq!(app.items.len(), 2);
+    }
+
+    #[test]
+    fn scope_merges_fetched_pages() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        let first = vec![item("1", "", "5"), item("2", "", "1")];
+        app.remember_page(&params, &first);
+        app.update_items(first);
+        params.next_page_by(1);
+        let second = vec![item("2", "", "1"), item("3", "", "9")];
+        app.remember_page(&params, &second);
+        app.update_items(second);
+        app.sort_by(SortKey::Seeders, false);
+        app.current = Some(1);
+
+        app.toggle_scope();
+        let ids: Vec<&str> = app.items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["3", "1", "2"]);
+        assert_eq!(app.selected_id().as_deref(), Some("2"));
+
+        app.toggle_scope();
+        assert_eq!(app.items.len(), 2);
+        assert_eq!(app.selected_id().as_deref(), Some("2"));
+
+        // a new search starts over
+        app.toggle_scope();
+        params.set_query("x");
+        app.remember_page(&params, &vec![item("4", "", "")]);
+        assert_eq!(app.pages.len(), 1);
+    }
+
+    #[test]
+    fn reconcile_selection_follows_the_item() {
+        let mut app = App::new();
+        app.update_items(vec![
+            item("1", "c", "1"),
+            item("2", "b", "2"),
+            item("3", "a", "3"),
+        ]);
+        app.next_by(0);
+        app.next_by(1);
+        app.sort_by(SortKey::Name, true);
+        assert_eq!(app.selected_id().as_deref(), Some("2"));
+        assert_eq!(app.current, Some(1));
+
+        // filtered out, so the old index is clamped
+        app.last_item();
+        let prev = app.selected_id();
+        app.items.truncate(1);
+        app.reconcile_selection(prev);
+        assert_eq!(app.current, Some(0));
+
+        app.items.clear();
+        app.reconcile_selection(None);
+        assert_eq!(app.current, None);
+        assert_eq!(app.state.selected(), None);
+    }
+
+    #[test]
+    fn marking_counts_rows_that_flip() {
+        let mut app = App::new();
+        app.update_items((1..=20).map(|i| item(&i.to_string(), "", "")).collect());
+        let ids: Vec<u64> = (1..=20).collect();
+        assert_eq!(app.marking_affects(&ids, true), 20);
+        app.last_id = 15;
+        assert_eq!(app.marking_affects(&ids, true), 5);
+        assert_eq!(app.marking_affects(&ids, false), 15);
+    }
+
+    #[test]
+    fn viewed_marks_are_per_item() {
+        let mut app = App::new();
+        app.saving = false;
+        let mut params = Params::new();
+        app.update_items(vec![
+            item("30", "", ""),
+            item("10", "", ""),
+            item("20", "", ""),
+        ]);
+        // the old threshold still counts
+        app.last_id = 10;
+        let viewed = |app: &App| {
+            app.items
+                .iter()
+                .map(|i| app.is_viewed(i))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(viewed(&app), [false, true, false]);
+
+        // s on 30 leaves 20 alone, and flips back off again
+        press(&mut app, &mut params, "gs");
+        assert_eq!(viewed(&app), [true, true, false]);
+        press(&mut app, &mut params, "s");
+        assert_eq!(viewed(&app), [false, true, false]);
+
+        // items under the old threshold can be unviewed too
+        press(&mut app, &mut params, "js");
+        assert_eq!(viewed(&app), [false, false, false]);
+
+        // with marked items, s covers all of them
+        press(&mut app, &mut params, "g jj ");
+        press(&mut app, &mut params, "s");
+        assert_eq!(viewed(&app), [true, false, true]);
+    }
+
+    #[test]
+    fn launches_are_debounced() {
+        let mut app = App::new();
+        let start = Instant::now();
+        assert!(app.may_launch(start));
+        assert!(!app.may_launch(start + Duration::from_millis(100)));
+        assert!(!app.may_launch(start + Duration::from_millis(299)));
+        assert!(app.may_launch(start + Duration::from_millis(300)));
+
+        app.launch_cooldown = Duration::ZERO;
+        assert!(app.may_launch(start + Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn marks_survive_paging() {
+        let mut app = App::new();
+        app.update_items(vec![item("1", "one", ""), item("2", "two", "")]);
+        app.toggle_mark();
+        assert!(app.marked.is_empty());
+
+        app.first_item();
+        app.toggle_mark();
+        app.next_by(1);
+        app.toggle_mark();
+        app.update_items(vec![item("3", "three", "")]);
+        assert_eq!(app.marked.len(), 2);
+        assert!(app.is_marked("2"));
+
+        app.update_items(vec![item("1", "one", "")]);
+        app.first_item();
+        app.toggle_mark();
+        assert_eq!(app.marked, [item("2", "two", "")]);
+    }
+
+    #[test]
+    fn marked_magnets_skip_items_without_one() {
+        let with_magnet = |id: &str| Response {
+            magnet: format!("magnet:?xt=urn:btih:{}", id),
+            ..item(id, "", "")
+        };
+        let items = [with_magnet("1"), item("2", "", ""), with_magnet("3")];
+        assert_eq!(
+            marked_magnets(&items),
+            ["magnet:?xt=urn:btih:1", "magnet:?xt=urn:btih:3"]
+        );
+    }
+
+    #[test]
+    fn save_search_replaces_by_name() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        params.set_query("one piece");
+        app.save_search("weekly", &params);
+        app.sort_by(SortKey::Seeders, false);
+        params.set_query("bleach");
+        params.set_category(Some("1"), None);
+        app.save_search("weekly", &params);
+
+        assert_eq!(
+            app.searches,
+            [SavedSearch {
+                name: "weekly".to_string(),
+                query: "bleach".to_string(),
+                category: Some("1".to_string()),
+                sub_category: None,
+                sort: Some((SortKey::Seeders, false)),
+                filter: 0,
+            }]
+        );
+
+        let mut other = App::new();
+        let mut other_params = Params::new();
+        app.searches[0].apply(&mut other, &mut other_params);
+        assert_eq!(other_params.query, "bleach");
+        assert_eq!(other_params.category.as_deref(), Some("1"));
+        assert_eq!(other.sort, Some((SortKey::Seeders, false)));
+    }
+
+    fn press(app: &mut App, params: &mut Params, keys: &str) -> Mode {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut mode = Mode::Normal;
+        for c in keys.chars() {
+            let next = handle_key(mode, KeyCode::Char(c), app, params);
+            mode = runtime.block_on(next).unwrap().unwrap();
+        }
+        mode
+    }
+
+    #[test]
+    fn counts_jump_with_g_and_big_g() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        app.update_items((0..20).map(|i| item(&i.to_string(), "", "")).collect());
+        press(&mut app, &mut params, "5g");
+        assert_eq!(app.current, Some(4));
+        press(&mut app, &mut params, "2G");
+        assert_eq!(app.current, Some(18));
+        press(&mut app, &mut params, "99g");
+        assert_eq!(app.current, Some(19));
+        press(&mut app, &mut params, "99G");
+        assert_eq!(app.current, Some(0));
+        press(&mut app, &mut params, "G");
+        assert_eq!(app.current, Some(19));
+        // the count is used up by the jump
+        press(&mut app, &mut params, "3gj");
+        assert_eq!(app.current, Some(3));
+    }
+
+    #[test]
+    fn counts_only_apply_to_the_next_command() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        app.update_items((0..20).map(|i| item(&i.to_string(), "", "")).collect());
+
+        assert!(matches!(press(&mut app, &mut params, "12"), Mode::PendingCount(c) if c == "12"));
+        assert!(matches!(press(&mut app, &mut params, "5gj"), Mode::Normal));
+        assert_eq!(app.current, Some(5));
+        assert!(matches!(press(&mut app, &mut params, "3j"), Mode::Normal));
+        assert_eq!(app.current, Some(8));
+    }
+
+    #[test]
+    fn detail_moves_between_items() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        app.update_items(vec![item("1", "", ""), item("2", "", "")]);
+
+        let mode = press(&mut app, &mut params, "d");
+        assert!(matches!(mode, Mode::Modal(Modal::Detail)));
+        assert_eq!(app.current, Some(0));
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Char('j'), &mut app, &mut params));
+        let mode = mode.unwrap().unwrap();
+        assert!(matches!(mode, Mode::Modal(Modal::Detail)));
+        assert_eq!(app.current, Some(1));
+
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Esc, &mut app, &mut params));
+        assert!(matches!(mode.unwrap().unwrap(), Mode::Normal));
+    }
+
+    #[test]
+    fn esc_drops_searches_and_counts() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        params.set_query("old");
+        app.update_items((0..10).map(|i| item(&i.to_string(), "", "")).collect());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let mode = press(&mut app, &mut params, "/new");
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Esc, &mut app, &mut params));
+        assert!(matches!(mode.unwrap().unwrap(), Mode::Normal));
+        assert_eq!(params.query, "old");
+        assert_eq!(app.items.len(), 10);
+
+        let mode = press(&mut app, &mut params, "g5");
+        let mode = runtime.block_on(handle_key(mode, KeyCode::Esc, &mut app, &mut params));
+        assert!(matches!(mode.unwrap().unwrap(), Mode::Normal));
+        press(&mut app, &mut params, "j");
+        assert_eq!(app.current, Some(1));
+    }
+
+    #[test]
+    fn the_cache_stands_in_while_offline() {
+        let mut app = App::new();
+        app.saving = false;
+        let mut params = Params::new();
+        let cache = Cache {
+            query: "one piece".to_string(),
+            page: 3,
+            fetched: unix_now() - 7200,
+            items: vec![item("1", "cached", "5")],
+        };
+        let path = std::env::temp_dir().join(format!("nyaa-cache-{}.json", std::process::id()));
+        save_cache(&path, &cache).unwrap();
+        assert_eq!(load_cache(&path), Some(cache.clone()));
+        std::fs::write(&path, "garbage").unwrap();
+        assert_eq!(load_cache(&path), None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(load_cache(&path), None);
+
+        app.show_cache(&mut params, cache);
+        assert_eq!((params.query.as_str(), params.page), ("one piece", 3));
+        assert_eq!(app.selected().unwrap().name, "cached");
+        assert_eq!(
+            status_line(&app, &params),
+            "offline, cached 2h ago | page 3 | 1 result | \"one piece\""
+        );
+
+        // a fetch that works again takes the banner down
+        let body = serde_json::to_string(&vec![item("2", "fresh", "1")]).unwrap();
+        app.client = nyaa::Client::with_base(&serve_once(body));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mode = runtime.block_on(reload(&mut app, &mut params));
+        assert!(matches!(mode, Mode::Normal));
+        assert_eq!(app.offline, None);
+        assert!(!status_line(&app, &params).starts_with("offline"));
+    }
+
+    #[test]
+    fn status_line_shows_page_results_and_query() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        app.update_items(vec![item("1", "", "3"), item("2", "", "30")]);
+        assert_eq!(status_line(&app, &params), "page 1 | 2 results | no query");
+
+        params.next_page_by(2);
+        params.set_query("one piece");
+        app.set_min_seeders(Some(10));
+        assert_eq!(
+            status_line(&app, &params),
+            "page 3 | 1 result (of 2) | \"one piece\""
+        );
+    }
+
+    #[test]
+    fn moving_around_an_empty_list_does_nothing() {
+        // App's moves by name, and keys to press for the rest
+        let moves = [
+            "g",
+            "G",
+            "j",
+            "k",
+            "5j",
+            "5k",
+            "last_item",
+            "next_by(1)",
+            "next_by(10)",
+            "previous_by(1)",
+            "first_item",
+        ];
+        for name in moves {
+            let mut app = App::new();
+            match name {
+                "last_item" => app.last_item(),
+                "next_by(1)" => app.next_by(1),
+                "next_by(10)" => app.next_by(10),
+                "previous_by(1)" => app.previous_by(1),
+                "first_item" => app.first_item(),
+                keys => {
+                    press(&mut app, &mut Params::new(), keys);
+                }
+            }
+            assert_eq!(app.current, None, "{}", name);
+            assert_eq!(app.state.selected(), None, "{}", name);
+            assert!(app.selected().is_none(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn centered_boxes_fit_the_area() {
+        let area = Rect::new(0, 0, 100, 40);
+        assert_eq!(centered(area, 60, 10), Rect::new(20, 15, 60, 10));
+        assert_eq!(centered(area, 200, 50), area);
+    }
+
+    #[test]
+    fn input_lines_edit_at_the_cursor() {
+        let mut text = String::new();
+        let mut cursor = 0;
+        let mut keys = |codes: &[KeyCode]| {
+            for code in codes {
+                edit_line(&mut text, &mut cursor, *code);
+            }
+            (text.clone(), cursor)
+        };
+        let typed: Vec<KeyCode> = "one pece".chars().map(KeyCode::Char).collect();
+        assert_eq!(keys(&typed), ("one pece".to_string(), 8));
+        let fix = [
+            KeyCode::Left,
+            KeyCode::Left,
+            KeyCode::Left,
+            KeyCode::Char('i'),
+        ];
+        assert_eq!(keys(&fix), ("one piece".to_string(), 6));
+        assert_eq!(
+            keys(&[KeyCode::Home, KeyCode::Delete]),
+            ("ne piece".to_string(), 0)
+        );
+        // nothing before the start or past the end
+        assert_eq!(
+            keys(&[KeyCode::Backspace, KeyCode::Left]),
+            ("ne piece".to_string(), 0)
+        );
+        assert_eq!(
+            keys(&[KeyCode::End, KeyCode::Right, KeyCode::Delete]),
+            ("ne piece".to_string(), 8)
+        );
+        // wide characters move and delete as one
+        let kana = [
+            KeyCode::Char('の'),
+            KeyCode::Left,
+            KeyCode::Right,
+            KeyCode::Backspace,
+        ];
+        assert_eq!(keys(&kana), ("ne piece".to_string(), 8));
+        assert_eq!(
+            keys(&[KeyCode::Left, KeyCode::Char('é')]),
+            ("ne piecée".to_string(), 9)
+        );
+    }
+
+    #[test]
+    fn input_mode_collects_text() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        let mode = press(&mut app, &mut params, "/3j");
+        assert!(matches!(mode, Mode::Input(Input::Search, text, 2) if text == "3j"));
+        assert_eq!(app.current, None);
+    }
+
+    #[test]
+    fn sparkline_scales_between_min_and_max() {
+        assert_eq!(sparkline(&[], false), "");
+        assert_eq!(sparkline(&[5, 5], false), "▄▄");
+        assert_eq!(sparkline(&[0, 7, 14], false), "▁▄█");
+        assert_eq!(sparkline(&[0, 7, 14], true), "_-#");
+    }
+
+    #[test]
+    fn trend_history_is_bounded() {
+        let mut app = App::new();
+        for seeders in 0..20 {
+            app.update_items(vec![item("1", "", &seeders.to_string())]);
+        }
+        assert_eq!(app.trend["1"], (12..20).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn retries_empty_only_on_the_first_query() {
+        let mut app = App::new();
+        let mut params = Params::new();
+        assert!(!app.should_retry_empty(&params, &vec![]));
+
+        params.set_query("one piece");
+        assert!(app.should_retry_empty(&params, &vec![]));
+        assert!(!app.should_retry_empty(&params, &vec![Response::default()]));
+
+        app.fetched = true;
+        assert!(!app.should_retry_empty(&params, &vec![]));
+
+        app.fetched = false;
+        app.retry_empty = false;
+        assert!(!app.should_retry_empty(&params, &vec![]));
+
+        // a typo answered with nothing is retried the first time only
+        let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = requests.clone();
+        let server = serve(move |stream, _, _| {
+            counted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            stream.write_all(&ok("", b"[]"))
+        });
+        app.saving = false;
+        app.retry_empty = true;
+        app.client = nyaa::Client::with_base(&server.url);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        params.set_query("one pcie");
+        runtime.block_on(fetch_items(&mut app, &params)).unwrap();
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 2);
+        runtime.block_on(fetch_items(&mut app, &params)).unwrap();
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
 }
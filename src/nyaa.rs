@@ -0,0 +1,686 @@
+// the nyaa api: what a search asks for, what comes back, and the client that
+// sends it. nothing in here knows about the tui
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const NYAA_URL: &str = "https://nyaa-api.fly.dev";
+
+// the last page nyaa will show for a search
+pub const MAX_PAGE: u16 = 1000;
+
+// how long a request gets before it's given up on as a timeout
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how many more times a transient failure is tried
+pub const DEFAULT_RETRIES: u32 = 2;
+
+// the requests per second every fetch is held to until set_rate_limit
+pub const DEFAULT_RATE_LIMIT: f64 = 2.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Params {
+    pub page: u16,
+    pub query: String,
+    // nyaa category ids, sent as c and s
+    pub category: Option<String>,
+    pub sub_category: Option<String>,
+    // sent as f: 0 for everything, 1 for no remakes, 2 for trusted only
+    pub filter: u8,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Self {
+            page: 1,
+            query: "".to_string(),
+            category: None,
+            sub_category: None,
+            filter: 0,
+        }
+    }
+
+    pub fn next_page_by(&mut self, amount: u16) {
+        self.page = page_after(self.page, amount)
+    }
+
+    pub fn prev_page_by(&mut self, amount: u16) {
+        self.page = page_before(self.page, amount)
+    }
+
+    pub fn set_query<S: Into<String> + std::fmt::Display>(&mut self, query: S) {
+        self.query = query.to_string();
+    }
+
+    // everything, then no remakes, then trusted only, then around again
+    pub fn cycle_filter(&mut self) {
+        self.filter = (self.filter + 1) % 3;
+    }
+
+    pub fn set_category(&mut self, category: Option<&str>, sub_category: Option<&str>) {
+        self.category = category.map(str::to_string);
+        self.sub_category = category.and(sub_category).map(str::to_string);
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub id: String,
+    pub name: String,
+    pub hash: String,
+    pub date: String,
+    pub filesize: String,
+    pub category: String,
+    pub sub_category: String,
+    pub magnet: String,
+    pub torrent: String,
+    #[serde(deserialize_with = "deserialize_count")]
+    pub seeders: String,
+    #[serde(deserialize_with = "deserialize_count")]
+    pub leechers: String,
+    #[serde(deserialize_with = "deserialize_count")]
+    pub completed: String,
+    pub status: String,
+}
+
+pub type Responses = Vec<Response>;
+
+// amount pages on, stopping at MAX_PAGE
+fn page_after(page: u16, amount: u16) -> u16 {
+    page.saturating_add(amount).min(MAX_PAGE)
+}
+
+// amount pages back, stopping at 1 since pages start there
+fn page_before(page: u16, amount: u16) -> u16 {
+    page.saturating_sub(amount).max(1)
+}
+
+// what a filter value means, None for no filter
+pub fn filter_label(filter: u8) -> Option<&'static str> {
+    match filter {
+        1 => Some("no remakes"),
+        2 => Some("trusted only"),
+        _ => None,
+    }
+}
+
+// parse a count like "1,234" or " 56 ", which the api sometimes sends
+pub fn parse_count(s: &str) -> Option<u64> {
+    let digits: String = s
+        .chars()
+        .filter(|c| *c != ',' && !c.is_whitespace())
+        .collect();
+    digits.parse::<u64>().ok()
+}
+
+// normalize count fields so later numeric parses don't trip on separators
+fn deserialize_count<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Count {
+        Number(u64),
+        Text(String),
+    }
+
+    Ok(match Count::deserialize(deserializer)? {
+        Count::Number(n) => n.to_string(),
+        Count::Text(s) => parse_count(&s).map(|n| n.to_string()).unwrap_or(s),
+    })
+}
+
+// everything that can go wrong asking the api for a page
+#[derive(Debug)]
+pub enum ApiError {
+    Network(reqwest::Error),
+    Timeout,
+    BadStatus(u16),
+    Parse(String),
+    RateLimited,
+}
+
+impl ApiError {
+    // worth another try after a pause: the backend may be back by then
+    fn is_transient(&self) -> bool {
+        match self {
+            ApiError::Network(_) | ApiError::Timeout | ApiError::RateLimited => true,
+            ApiError::BadStatus(code) => (500..600).contains(code),
+            ApiError::Parse(_) => false,
+        }
+    }
+
+    fn from_status(status: reqwest::StatusCode) -> Self {
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            ApiError::RateLimited
+        } else {
+            ApiError::BadStatus(status.as_u16())
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::Network(e) => write!(f, "network error: {}", e),
+            ApiError::Timeout => write!(f, "request timed out"),
+            ApiError::BadStatus(code) => write!(f, "api answered with status {}", code),
+            ApiError::Parse(e) => write!(f, "couldn't read the api's answer: {}", e),
+            ApiError::RateLimited => write!(f, "rate limited by the api"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ApiError::Timeout
+        } else if let Some(status) = e.status() {
+            ApiError::from_status(status)
+        } else if e.is_decode() {
+            ApiError::Parse(e.to_string())
+        } else {
+            ApiError::Network(e)
+        }
+    }
+}
+
+// one page of results, and how long the request took once it was sent
+#[derive(Debug)]
+pub struct SearchResult {
+    pub items: Responses,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone)]
+pub struct Client {
+    // built on first use, and again when its pooled connections go stale
+    http: Arc<Mutex<Option<reqwest::Client>>>,
+    base: String,
+    // how many more times a transient failure is tried, backing off each time
+    retries: u32,
+    timeout: Duration,
+    // ask for gzip or deflate bodies, which big pages shrink a lot under.
+    // servers that ignore it just send them as they are
+    compression: bool,
+    // retries don't show in retrying(), for fetches the user isn't waiting on
+    background: bool,
+}
+
+fn http_client(timeout: Duration, compression: bool) -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .gzip(compression)
+        .deflate(compression)
+        .build()
+}
+
+impl Client {
+    // base is the nyaa-api instance to talk to, like NYAA_URL
+    pub fn with_base(base: &str) -> Self {
+        Self {
+            http: Arc::new(Mutex::new(None)),
+            base: base.to_string(),
+            retries: DEFAULT_RETRIES,
+            timeout: DEFAULT_TIMEOUT,
+            compression: true,
+            background: false,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.http = Arc::new(Mutex::new(None));
+        self
+    }
+
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self.http = Arc::new(Mutex::new(None));
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+
+    fn set_retrying(&self, attempt: u32) {
+        if !self.background {
+            RETRYING.store(attempt, Ordering::Relaxed);
+        }
+    }
+
+    // the shared client, built with the timeout now if it hasn't been. a
+    // failed build is an error rather than a client without the timeout
+    fn http(&self) -> Result<reqwest::Client, ApiError> {
+        let mut http = match self.http.lock() {
+            Ok(http) => http,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(client) = http.as_ref() {
+            return Ok(client.clone());
+        }
+        let client = http_client(self.timeout, self.compression)?;
+        *http = Some(client.clone());
+        Ok(client)
+    }
+
+    // drop the connection pool, e.g. after the machine slept
+    fn reconnect(&self) {
+        if let Ok(mut http) = self.http.lock() {
+            *http = None;
+        }
+    }
+
+    // the exact url search requests, so it can be shown to the user too
+    pub fn url(&self, params: &Params) -> Result<reqwest::Url, ApiError> {
+        let mut url = reqwest::Url::parse_with_params(
+            &self.base,
+            &[("p", params.page.to_string()), ("q", params.query.clone())],
+        )
+        .map_err(|e| ApiError::Parse(e.to_string()))?;
+        if let Some(category) = &params.category {
+            url.query_pairs_mut().append_pair("c", category);
+        }
+        if let Some(sub_category) = &params.sub_category {
+            url.query_pairs_mut().append_pair("s", sub_category);
+        }
+        if params.filter != 0 {
+            url.query_pairs_mut()
+                .append_pair("f", &params.filter.to_string());
+        }
+        Ok(url)
+    }
+
+    // fetch a page, trying transient failures again after 0.5s, 1s, 2s...
+    // up to the client's retries. retrying() says which retry is waiting,
+    // unless the client is a background one
+    pub async fn search(&self, params: &Params) -> Result<SearchResult, ApiError> {
+        let mut attempt = 0;
+        loop {
+            match self.search_once(params).await {
+                Err(e) if attempt < self.retries && e.is_transient() => {
+                    attempt += 1;
+                    self.set_retrying(attempt);
+                    tokio::time::sleep(backoff(attempt)).await;
+                }
+                result => {
+                    self.set_retrying(0);
+                    return result;
+                }
+            }
+        }
+    }
+
+    // waiting on the shared rate limiter first. a connection that went stale
+    // (after a suspend, say) gets one retry on a fresh pool straight away
+    async fn search_once(&self, params: &Params) -> Result<SearchResult, ApiError> {
+        match self.request(params).await {
+            Err(ApiError::Network(e)) if e.is_connect() || e.is_request() => {
+                self.reconnect();
+                self.request(params).await
+            }
+            result => result,
+        }
+    }
+
+    // fetch any file over the same connections, like a .torrent from nyaa.si.
+    // it's read a chunk at a time so download_progress can follow along
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>, ApiError> {
+        let mut res = self.http()?.get(url).send().await?;
+        if !res.status().is_success() {
+            return Err(ApiError::from_status(res.status()));
+        }
+        let size = res.content_length();
+        let mut bytes = vec![];
+        set_download_progress(Some((0, size)));
+        let result = loop {
+            match res.chunk().await {
+                Ok(Some(chunk)) => {
+                    bytes.extend_from_slice(&chunk);
+                    set_download_progress(Some((bytes.len() as u64, size)));
+                }
+                Ok(None) => break Ok(bytes),
+                Err(e) => break Err(e.into()),
+            }
+        };
+        set_download_progress(None);
+        result
+    }
+
+    async fn request(&self, params: &Params) -> Result<SearchResult, ApiError> {
+        rate_limit().await;
+        let start = Instant::now();
+        let res = self.http()?.get(self.url(params)?).send().await?;
+        if !res.status().is_success() {
+            return Err(ApiError::from_status(res.status()));
+        }
+        let items = res.json::<Responses>().await?;
+
+        Ok(SearchResult {
+            items,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+// which retry a search is on, 0 when none is
+static RETRYING: AtomicU32 = AtomicU32::new(0);
+
+pub fn retrying() -> Option<u32> {
+    Some(RETRYING.load(Ordering::Relaxed)).filter(|attempt| *attempt > 0)
+}
+
+// bytes so far and the size, if the server said, of the download under way
+static DOWNLOAD_PROGRESS: Mutex<Option<(u64, Option<u64>)>> = Mutex::new(None);
+
+pub fn download_progress() -> Option<(u64, Option<u64>)> {
+    *DOWNLOAD_PROGRESS.lock().unwrap()
+}
+
+fn set_download_progress(progress: Option<(u64, Option<u64>)>) {
+    *DOWNLOAD_PROGRESS.lock().unwrap() = progress;
+}
+
+// how long to wait before the nth retry
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(500) * 2u32.saturating_pow(attempt.saturating_sub(1).min(10))
+}
+
+// a token bucket holding up to a second's worth of requests
+struct RateLimiter {
+    per_second: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(per_second: f64) -> Self {
+        Self {
+            per_second,
+            tokens: per_second.max(1.0),
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.per_second.max(1.0));
+        self.updated = now;
+    }
+
+    // how long until a request could go out, without taking a token
+    fn wait_time(&mut self, now: Instant) -> Duration {
+        if self.per_second <= 0.0 {
+            return Duration::ZERO;
+        }
+        self.refill(now);
+        Duration::from_secs_f64((1.0 - self.tokens).max(0.0) / self.per_second)
+    }
+
+    // take a token, returning how long to wait before using it. tokens can go
+    // negative, so requests that queue up behind each other wait their turn
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let wait = self.wait_time(now);
+        if self.per_second > 0.0 {
+            self.tokens -= 1.0;
+        }
+        wait
+    }
+}
+
+// shared by every client, so separate searches can't add up to a burst
+fn rate_limiter() -> &'static std::sync::Mutex<RateLimiter> {
+    static LIMITER: std::sync::OnceLock<std::sync::Mutex<RateLimiter>> = std::sync::OnceLock::new();
+    LIMITER.get_or_init(|| std::sync::Mutex::new(RateLimiter::new(DEFAULT_RATE_LIMIT)))
+}
+
+// hold every fetch from here on to per_second requests, 0 for no limit
+pub fn set_rate_limit(per_second: f64) {
+    if let Ok(mut limiter) = rate_limiter().lock() {
+        *limiter = RateLimiter::new(per_second);
+    }
+}
+
+// whether the next search would have to wait
+pub fn rate_limited() -> bool {
+    match rate_limiter().lock() {
+        Ok(mut limiter) => !limiter.wait_time(Instant::now()).is_zero(),
+        Err(_) => false,
+    }
+}
+
+async fn rate_limit() {
+    let wait = match rate_limiter().lock() {
+        Ok(mut limiter) => limiter.reserve(Instant::now()),
+        Err(_) => Duration::ZERO,
+    };
+    tokio::time::sleep(wait).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ok, serve};
+    use std::io::Write;
+
+    #[test]
+    fn parse_count_strips_separators() {
+        assert_eq!(parse_count("1,234"), Some(1234));
+        assert_eq!(parse_count(" 56 "), Some(56));
+        assert_eq!(parse_count(""), None);
+    }
+
+    #[test]
+    fn counts_are_normalized_on_deserialize() {
+        let json = r#"{
+            "id": "1", "name": "", "hash": "", "date": "", "filesize": "",
+            "category": "", "sub_category": "", "magnet": "", "torrent": "",
+            "seeders": "1,234", "leechers": " 56 ", "completed": "",
+            "status": ""
+        }"#;
+        let res: Response = serde_json::from_str(json).unwrap();
+        assert_eq!(res.seeders, "1234");
+        assert_eq!(res.leechers, "56");
+        assert_eq!(res.completed, "");
+    }
+
+    #[test]
+    fn page_arithmetic_stays_in_range() {
+        assert_eq!(page_after(1, 1), 2);
+        assert_eq!(page_after(998, 1), 999);
+        assert_eq!(page_after(999, 1), MAX_PAGE);
+        assert_eq!(page_after(MAX_PAGE, 1), MAX_PAGE);
+        assert_eq!(page_after(1, 999), MAX_PAGE);
+        assert_eq!(page_after(1, 1000), MAX_PAGE);
+        assert_eq!(page_after(500, u16::MAX), MAX_PAGE);
+        assert_eq!(page_after(3, 0), 3);
+
+        assert_eq!(page_before(2, 1), 1);
+        assert_eq!(page_before(1, 1), 1);
+        assert_eq!(page_before(5, 5), 1);
+        assert_eq!(page_before(MAX_PAGE, 999), 1);
+        assert_eq!(page_before(MAX_PAGE, u16::MAX), 1);
+        assert_eq!(page_before(3, 0), 3);
+        // a page 0 from somewhere else is pulled back up
+        assert_eq!(page_before(0, 0), 1);
+    }
+
+    #[test]
+    fn prev_page_stops_at_the_first_page() {
+        let mut params = Params::new();
+        params.next_page_by(4);
+        params.prev_page_by(2);
+        assert_eq!(params.page, 3);
+        params.prev_page_by(2);
+        assert_eq!(params.page, 1);
+        params.prev_page_by(1);
+        assert_eq!(params.page, 1);
+        params.next_page_by(4);
+        params.prev_page_by(u16::MAX);
+        assert_eq!(params.page, 1);
+    }
+
+    #[test]
+    fn request_url_encodes_query_and_page() {
+        let mut params = Params::new();
+        params.next_page_by(2);
+        params.set_query("one piece&x");
+        let url = Client::with_base(NYAA_URL).url(&params).unwrap();
+        assert_eq!(url.as_str(), format!("{}/?p=3&q=one+piece%26x", NYAA_URL));
+    }
+
+    #[test]
+    fn request_url_sends_categories() {
+        let client = Client::with_base("http://localhost:8080");
+        let mut params = Params::new();
+        params.set_category(Some("1"), Some("2"));
+        let url = client.url(&params).unwrap();
+        assert_eq!(url.as_str(), "http://localhost:8080/?p=1&q=&c=1&s=2");
+
+        params.set_category(None, Some("2"));
+        assert_eq!(params.sub_category, None);
+        let url = client.url(&params).unwrap();
+        assert_eq!(url.as_str(), "http://localhost:8080/?p=1&q=");
+
+        params.cycle_filter();
+        params.cycle_filter();
+        assert_eq!(filter_label(params.filter), Some("trusted only"));
+        let url = client.url(&params).unwrap();
+        assert_eq!(url.as_str(), "http://localhost:8080/?p=1&q=&f=2");
+        params.cycle_filter();
+        assert_eq!(params.filter, 0);
+    }
+
+    #[tokio::test]
+    async fn connection_errors_are_network_errors() {
+        // nothing listens on port 1, so both tries are refused
+        let client = Client::with_base("http://127.0.0.1:1").retries(0);
+        let result = client.search(&Params::new()).await;
+        assert!(matches!(result, Err(ApiError::Network(_))));
+    }
+
+    #[tokio::test]
+    async fn dropped_connections_are_tried_again_on_a_fresh_pool() {
+        let server = serve(|_, _, _| Err(std::io::ErrorKind::ConnectionAborted.into()));
+        let client = Client::with_base(&server.url).retries(0);
+        let result = client.search(&Params::new()).await;
+        assert!(matches!(result, Err(ApiError::Network(_))));
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn hanging_backends_time_out() {
+        // the server never answers, so the request can only time out
+        let server = serve(|_, _, _| {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(())
+        });
+        let client = Client::with_base(&server.url)
+            .retries(0)
+            .timeout(Duration::from_millis(100));
+        let result = client.search(&Params::new()).await;
+        assert!(matches!(result, Err(ApiError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn downloads_read_the_whole_body() {
+        // half the body, a pause to watch the progress in, then the rest
+        let server = serve(|stream, _, _| {
+            let response = ok("", &[b'x'; 100_000]);
+            let (first, rest) = response.split_at(response.len() - 50_000);
+            stream.write_all(first)?;
+            stream.flush()?;
+            std::thread::sleep(Duration::from_millis(300));
+            stream.write_all(rest)
+        });
+        let url = format!("{}/1.torrent", server.url);
+        let download =
+            tokio::spawn(async move { Client::with_base(NYAA_URL).download(&url).await });
+        // other tests can download at the same time, theirs aren't this size
+        let mut partial = None;
+        while !download.is_finished() {
+            if let Some((n, Some(100_000))) = download_progress() {
+                partial = partial.or(Some(n).filter(|n| (1..100_000).contains(n)));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(partial.is_some());
+        assert_eq!(download.await.unwrap().unwrap().len(), 100_000);
+        assert!(!matches!(download_progress(), Some((_, Some(100_000)))));
+    }
+
+    #[tokio::test]
+    async fn compressed_answers_are_decoded() {
+        use flate2::{write::GzEncoder, Compression};
+        // gzip for clients that ask for it, an empty page for those that don't
+        let server = serve(|stream, request, _| {
+            if !request.to_lowercase().contains("accept-encoding: gzip") {
+                return stream.write_all(&ok("", b"[]"));
+            }
+            let page = serde_json::to_vec(&vec![Response::default()]).unwrap();
+            let mut gzip = GzEncoder::new(vec![], Compression::default());
+            gzip.write_all(&page).unwrap();
+            let body = gzip.finish().unwrap();
+            stream.write_all(&ok("Content-Encoding: gzip\r\n", &body))
+        });
+        let client = Client::with_base(&server.url);
+        let result = client.search(&Params::new()).await.unwrap();
+        assert_eq!(result.items, vec![Response::default()]);
+
+        let client = client.compression(false);
+        let result = client.search(&Params::new()).await.unwrap();
+        assert_eq!(result.items, vec![]);
+    }
+
+    #[test]
+    fn statuses_map_to_api_errors() {
+        let error = ApiError::from_status(reqwest::StatusCode::TOO_MANY_REQUESTS);
+        assert!(matches!(error, ApiError::RateLimited));
+        let error = ApiError::from_status(reqwest::StatusCode::BAD_GATEWAY);
+        assert!(matches!(error, ApiError::BadStatus(502)));
+        assert_eq!(error.to_string(), "api answered with status 502");
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        assert!(ApiError::Timeout.is_transient());
+        assert!(ApiError::BadStatus(502).is_transient());
+        assert!(ApiError::RateLimited.is_transient());
+        assert!(!ApiError::BadStatus(404).is_transient());
+        assert!(!ApiError::Parse("eof".to_string()).is_transient());
+
+        assert_eq!(backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff(2), Duration::from_secs(1));
+        assert_eq!(backoff(4), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn rate_limiter_queues_requests() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(2.0);
+        limiter.updated = start;
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+        assert_eq!(limiter.reserve(start), Duration::from_millis(500));
+        assert_eq!(limiter.reserve(start), Duration::from_millis(1000));
+        let later = start + Duration::from_secs(10);
+        assert_eq!(limiter.wait_time(later), Duration::ZERO);
+
+        let mut unlimited = RateLimiter::new(0.0);
+        for _ in 0..10 {
+            assert_eq!(unlimited.reserve(start), Duration::ZERO);
+        }
+    }
+}
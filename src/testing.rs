@@ -0,0 +1,86 @@
+// a local http server for the tests that need something to talk to
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub struct Server {
+    pub url: String,
+    connections: Arc<AtomicUsize>,
+}
+
+impl Server {
+    // how many connections have been accepted so far
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+// answer every request with respond, which gets the stream to write to, the
+// request's headers and its number, counting from 0 across connections.
+// connections are kept alive until the client closes them or respond fails
+pub fn serve<F>(respond: F) -> Server
+where
+    F: Fn(&mut TcpStream, &str, usize) -> io::Result<()> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let respond = Arc::new(respond);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let (respond, requests) = (respond.clone(), requests.clone());
+            std::thread::spawn(move || {
+                while let Some(request) = read_request(&mut stream) {
+                    let n = requests.fetch_add(1, Ordering::SeqCst);
+                    if respond(&mut stream, &request, n).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    Server { url, connections }
+}
+
+// read a request up to the end of its headers, None once the client is gone
+fn read_request(stream: &mut TcpStream) -> Option<String> {
+    let mut request = vec![];
+    let mut buf = [0; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+    Some(String::from_utf8_lossy(&request).into_owned())
+}
+
+// a 200 carrying body, headers are extra lines like "Content-Type: x\r\n"
+pub fn ok(headers: &str, body: &[u8]) -> Vec<u8> {
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n{}\r\n",
+        body.len(),
+        headers
+    );
+    [head.as_bytes(), body].concat()
+}
+
+// answers the first request with body as json and drops the connection of
+// any after it. the url to reach it at
+pub fn serve_once(body: String) -> String {
+    serve(move |stream, _, n| {
+        if n > 0 {
+            return Err(io::ErrorKind::ConnectionAborted.into());
+        }
+        stream.write_all(&ok("Content-Type: application/json\r\n", body.as_bytes()))
+    })
+    .url
+}
@@ -0,0 +1,182 @@
+// colors for the table and popups, set under [colors] in ~/.nyaa.toml like
+// `header_bg = "black"`. a color is a name like red or lightblue, a terminal
+// color number from 0 to 255, or #rrggbb. anything unset or unreadable keeps
+// the default, which is how it always looked
+use crate::config::Config;
+use tui::style::{Color, Modifier, Style};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub header_fg: Color,
+    pub header_bg: Color,
+    // the selected row, reversed colors while neither is set
+    pub selected_fg: Option<Color>,
+    pub selected_bg: Option<Color>,
+    // rows marked with space
+    pub marked: Color,
+    // the viewed markers, only visible on the ascii ones
+    pub viewed: Option<Color>,
+    pub unviewed: Option<Color>,
+    // the search bar and help text, and the borders around them and the table
+    pub text: Option<Color>,
+    pub border: Option<Color>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            header_fg: Color::Red,
+            header_bg: Color::Blue,
+            selected_fg: None,
+            selected_bg: None,
+            marked: Color::Yellow,
+            viewed: None,
+            unviewed: None,
+            text: None,
+            border: None,
+        }
+    }
+}
+
+impl Theme {
+    pub fn from_config(config: &Config) -> Self {
+        let color = |key: &str| config.get(&format!("colors.{}", key)).and_then(parse_color);
+        let default = Self::default();
+        Self {
+            header_fg: color("header").unwrap_or(default.header_fg),
+            header_bg: color("header_bg").unwrap_or(default.header_bg),
+            selected_fg: color("selected"),
+            selected_bg: color("selected_bg"),
+            marked: color("marked").unwrap_or(default.marked),
+            viewed: color("viewed"),
+            unviewed: color("unviewed"),
+            text: color("text"),
+            border: color("border"),
+        }
+    }
+
+    pub fn header(&self) -> Style {
+        Style::default().fg(self.header_fg)
+    }
+
+    pub fn header_row(&self) -> Style {
+        Style::default().bg(self.header_bg)
+    }
+
+    pub fn selected(&self) -> Style {
+        match (self.selected_fg, self.selected_bg) {
+            (None, None) => Style::default().add_modifier(Modifier::REVERSED),
+            (fg, bg) => with_bg(with_fg(Style::default(), fg), bg),
+        }
+    }
+
+    pub fn marked(&self) -> Style {
+        Style::default().fg(self.marked)
+    }
+
+    pub fn viewed_marker(&self, viewed: bool) -> Style {
+        with_fg(
+            Style::default(),
+            if viewed { self.viewed } else { self.unviewed },
+        )
+    }
+
+    pub fn text(&self) -> Style {
+        with_fg(Style::default(), self.text)
+    }
+
+    pub fn border(&self) -> Style {
+        with_fg(Style::default(), self.border)
+    }
+}
+
+fn with_fg(style: Style, color: Option<Color>) -> Style {
+    match color {
+        Some(color) => style.fg(color),
+        None => style,
+    }
+}
+
+fn with_bg(style: Style, color: Option<Color>) -> Style {
+    match color {
+        Some(color) => style.bg(color),
+        None => style,
+    }
+}
+
+// "red", "light-blue", "dark gray", "208" or "#ff8700"
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value
+        .trim()
+        .to_ascii_lowercase()
+        .replace(['-', '_', ' '], "");
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let n = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::Rgb((n >> 16) as u8, (n >> 8) as u8, n as u8));
+    }
+    if let Ok(n) = value.parse::<u8>() {
+        return Some(Color::Indexed(n));
+    }
+    Some(match value.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_parse_names_numbers_and_hex() {
+        assert_eq!(parse_color("Red"), Some(Color::Red));
+        assert_eq!(parse_color("light-blue"), Some(Color::LightBlue));
+        assert_eq!(parse_color("dark gray"), Some(Color::DarkGray));
+        assert_eq!(parse_color("208"), Some(Color::Indexed(208)));
+        assert_eq!(parse_color("#ff8700"), Some(Color::Rgb(255, 135, 0)));
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("256"), None);
+        assert_eq!(parse_color("mauve"), None);
+    }
+
+    #[test]
+    fn unset_colors_keep_the_defaults() {
+        assert_eq!(Theme::from_config(&Config::default()), Theme::default());
+        let reversed = Style::default().add_modifier(Modifier::REVERSED);
+        assert_eq!(Theme::default().selected(), reversed);
+
+        let config = Config::parse(
+            r##"
+            [colors]
+            header = "black"
+            header_bg = "#eeeeee"
+            selected_bg = "lightyellow"
+            marked = "nope"
+            "##,
+        );
+        let theme = Theme::from_config(&config);
+        assert_eq!(theme.header_fg, Color::Black);
+        assert_eq!(theme.header_bg, Color::Rgb(0xee, 0xee, 0xee));
+        assert_eq!(theme.selected(), Style::default().bg(Color::LightYellow));
+        assert_eq!(theme.marked, Color::Yellow);
+    }
+}